@@ -0,0 +1,60 @@
+//! Times the parsers on the example sentences.
+//!
+//! Run with `cargo bench -p kxparser`.
+use std::time::{Duration, Instant};
+
+use kxparser::parser::{self, BeamEarley, Earley1, Earley2, Earley3, Parser};
+
+const RUNS: u32 = 5;
+
+fn time<'g>(
+    name: &str,
+    parser: &impl Parser<'g>,
+    example: fn(usize) -> Vec<&'static str>,
+    n: usize,
+) {
+    let sentence = example(n);
+    let mut total = Duration::new(0, 0);
+    let mut edges = 0;
+    for _ in 0..RUNS {
+        let now = Instant::now();
+        let chart = parser.parse(&sentence);
+        total += now.elapsed();
+        edges = chart.chartsize();
+    }
+    println!(
+        "{:11} n = {:3}: {:>12.3?} per parse, {} edges",
+        name,
+        n,
+        total / RUNS,
+        edges,
+    );
+}
+
+fn main() {
+    let grammar = parser::example_grammar();
+    let earley1 = Earley1::from_grammar(&grammar);
+    let earley2 = Earley2::from_grammar(&grammar);
+    let earley3 = Earley3::from_grammar(&grammar);
+    let beam = BeamEarley::from_grammar(&grammar).with_beam_width(20);
+
+    println!("example(n)");
+    for &n in &[5, 10, 20] {
+        time("earley1", &earley1, parser::example, n);
+        time("earley2", &earley2, parser::example, n);
+        time("earley3", &earley3, parser::example, n);
+        time("beam(20)", &beam, parser::example, n);
+    }
+
+    let grammar = parser::right_recursive_grammar();
+    let earley2 = Earley2::from_grammar(&grammar);
+    let earley3 = Earley3::from_grammar(&grammar);
+    let leo = Earley3::from_grammar(&grammar).with_leo(true);
+
+    println!("right_recursive_example(n)");
+    for &n in &[50, 100, 200] {
+        time("earley2", &earley2, parser::right_recursive_example, n);
+        time("earley3", &earley3, parser::right_recursive_example, n);
+        time("earley3+leo", &leo, parser::right_recursive_example, n);
+    }
+}
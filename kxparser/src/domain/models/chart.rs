@@ -1,19 +1,164 @@
+use std::collections::HashMap;
+
 use crate::domain::models::edge::Edge;
 
-#[derive(Debug)]
+/// The passive edges found by a parser, grouped by end position.
+///
+/// All edges live in one vector; `offsets[k]` is the index of the first
+/// edge ending in position `k`, so the edgeset for a position is a slice
+/// and no per-position vectors are allocated.
+#[derive(Debug, Default)]
 pub struct Chart<'a> {
-    pub chart: Vec<Vec<Edge<'a>>>,
+    edges: Vec<Edge<'a>>,
+    offsets: Vec<usize>,
 }
 
-impl Chart<'_> {
+impl<'a> Chart<'a> {
     pub fn new() -> Self {
-        Chart { chart: Vec::new() }
+        Chart { edges: Vec::new(), offsets: Vec::new() }
+    }
+
+    pub fn with_capacity(positions: usize, edges: usize) -> Self {
+        Chart {
+            edges: Vec::with_capacity(edges),
+            offsets: Vec::with_capacity(positions),
+        }
+    }
+
+    /// Starts the edgeset for the next position.
+    pub fn add_position(&mut self) {
+        self.offsets.push(self.edges.len());
+    }
+
+    /// Adds an edge ending in the last position.
+    pub fn add_edge(&mut self, edge: Edge<'a>) {
+        debug_assert!(!self.offsets.is_empty(), "add_edge called before add_position");
+        self.edges.push(edge);
+    }
+
+    /// The number of positions, i.e. the number of words plus one.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// The edges ending in position `k`.
+    pub fn edgeset(&self, k: usize) -> &[Edge<'a>] {
+        let start = self.offsets[k];
+        let end = self.offsets.get(k + 1).copied().unwrap_or(self.edges.len());
+        &self.edges[start..end]
+    }
+
+    pub fn edgesets(&self) -> impl Iterator<Item = &[Edge<'a>]> {
+        (0..self.len()).map(move |k| self.edgeset(k))
+    }
+
+    pub fn edges(&self) -> &[Edge<'a>] {
+        &self.edges
     }
 
     pub fn chartsize(&self) -> usize {
-        self.chart.iter().map(|v| v.len()).sum()
+        self.edges.len()
+    }
+
+    /// Whether there is a passive `cat` edge from `start` to `end`.
+    ///
+    /// A negative `end` counts from the last position, so `-1` is the end
+    /// of the input. An empty span is recognized if a category derives the
+    /// empty string, as the chart parsers add edges for those.
+    pub fn recognizes(&self, cat: &str, start: usize, end: i32) -> bool {
+        let end = if end < 0 { self.len() as i64 + end as i64 } else { end as i64 };
+        if end < 0 || end >= self.len() as i64 {
+            return false;
+        }
+        self.edgeset(end as usize).iter()
+            .any(|edge| edge.start() == start && edge.lhs() == cat && edge.is_passive())
     }
 
+    /// The edges spanning from `start` to `end`.
+    pub fn span(&self, start: usize, end: usize) -> impl Iterator<Item = &Edge<'a>> {
+        let edges = if end < self.len() { self.edgeset(end) } else { &[] };
+        edges.iter().filter(move |edge| edge.start() == start)
+    }
+
+    /// Indexes the edges by category and start position for repeated
+    /// queries.
+    pub fn index(&self) -> ChartIndex<'_, 'a> {
+        ChartIndex::new(self)
+    }
+}
+
+/// The edges of a chart indexed by category, start position and span.
+///
+/// Edges are grouped by end position in the chart itself, so only the
+/// other lookups need an index. The index refers to the edges by their
+/// position in `Chart::edges`.
+pub struct ChartIndex<'c, 'a> {
+    chart: &'c Chart<'a>,
+    by_category: HashMap<&'a str, Vec<usize>>,
+    by_start: Vec<Vec<usize>>,
+}
+
+impl<'c, 'a> ChartIndex<'c, 'a> {
+    pub fn new(chart: &'c Chart<'a>) -> Self {
+        let mut by_category = HashMap::new();
+        let mut by_start = vec!(Vec::new(); chart.len());
+        for (i, edge) in chart.edges.iter().enumerate() {
+            by_category.entry(edge.lhs()).or_insert_with(Vec::new).push(i);
+            by_start[edge.start()].push(i);
+        }
+        ChartIndex { chart, by_category, by_start }
+    }
+
+    /// The passive edges for `cat`, ordered by end position.
+    pub fn category(&self, cat: &str) -> impl Iterator<Item = &'c Edge<'a>> + '_ {
+        self.edges(self.by_category.get(cat).map_or(&[][..], |edges| edges))
+    }
+
+    /// The edges starting in position `start`, ordered by end position.
+    pub fn starting_at(&self, start: usize) -> impl Iterator<Item = &'c Edge<'a>> + '_ {
+        self.edges(self.by_start.get(start).map_or(&[][..], |edges| edges))
+    }
+
+    /// The edges ending in position `end`.
+    pub fn ending_at(&self, end: usize) -> &'c [Edge<'a>] {
+        if end < self.chart.len() { self.chart.edgeset(end) } else { &[] }
+    }
+
+    /// The edges spanning from `start` to `end`, see `Chart::span`.
+    pub fn span(&self, start: usize, end: usize) -> impl Iterator<Item = &'c Edge<'a>> {
+        self.chart.span(start, end)
+    }
+
+    /// The constituents whose span is not inside the span of another
+    /// constituent, ordered by position. The edges of scanned words are
+    /// not constituents.
+    pub fn maximal_constituents(&self) -> Vec<&'c Edge<'a>> {
+        let is_constituent = |edge: &Edge| !(edge.rhs().is_empty() && edge.end() == edge.start() + 1);
+        let mut result = Vec::new();
+        // the furthest end of the constituents starting before or in the current position
+        let mut furthest = None;
+        for starting in &self.by_start {
+            let edges: Vec<&Edge<'a>> = self.edges(starting).filter(|edge| is_constituent(edge)).collect();
+            let end = match edges.last() {
+                Some(edge) => edge.end(),
+                None => continue,
+            };
+            if furthest.is_none_or(|furthest| end > furthest) {
+                result.extend(edges.into_iter().filter(|edge| edge.end() == end));
+                furthest = Some(end);
+            }
+        }
+        result
+    }
+
+    fn edges<'s>(&'s self, indices: &'s [usize]) -> impl Iterator<Item = &'c Edge<'a>> + 's {
+        let edges = &self.chart.edges;
+        indices.iter().map(move |&i| &edges[i])
+    }
 }
 
 #[cfg(test)]
@@ -25,7 +170,7 @@ mod tests {
         let chart = Chart::new();
 
         // assert_eq!(chart.chartsize(), 0);
-        assert_eq!(chart.chart.len(), 0);
+        assert_eq!(chart.len(), 0);
     }
 
     #[test]
@@ -34,4 +179,88 @@ mod tests {
 
         assert_eq!(chart.chartsize(), 0);
     }
+
+    #[test]
+    fn edges_are_grouped_by_position() {
+        let mut chart = Chart::new();
+        chart.add_position();
+        chart.add_position();
+        chart.add_edge(Edge::new(0, 1, "the", None, 0));
+        chart.add_edge(Edge::new(0, 1, "Det", None, 0));
+        chart.add_position();
+        chart.add_position();
+        chart.add_edge(Edge::new(2, 3, "sees", None, 0));
+
+        assert_eq!(chart.len(), 4);
+        assert_eq!(chart.chartsize(), 3);
+        assert!(chart.edgeset(0).is_empty());
+        assert_eq!(chart.edgeset(1).len(), 2);
+        assert!(chart.edgeset(2).is_empty());
+        assert_eq!(chart.edgeset(3), &[Edge::new(2, 3, "sees", None, 0)]);
+        let sizes: Vec<usize> = chart.edgesets().map(|edges| edges.len()).collect();
+        assert_eq!(sizes, vec!(0, 2, 0, 1));
+    }
+
+    #[test]
+    fn recognizes_any_span() {
+        let rhs = vec!("Det".to_string(), "Noun".to_string());
+        let mut chart = Chart::new();
+        chart.add_position();
+        chart.add_position();
+        chart.add_edge(Edge::new(0, 1, "the", None, 0));
+        chart.add_position();
+        chart.add_edge(Edge::new(0, 2, "NP", Some(&rhs), 2));
+        chart.add_edge(Edge::new(1, 2, "NP", Some(&rhs), 1));
+
+        assert!(chart.recognizes("NP", 0, 2));
+        assert!(chart.recognizes("NP", 0, -1));
+        assert!(!chart.recognizes("NP", 1, -1));
+        assert!(!chart.recognizes("NP", 0, 1));
+        assert!(!chart.recognizes("NP", 0, 3));
+        assert!(!chart.recognizes("NP", 0, -4));
+        assert!(!Chart::new().recognizes("NP", 0, -1));
+    }
+
+    fn np_chart(rhs: &[String]) -> Chart<'_> {
+        // "the lion": Det, Noun and NP, plus a stray Noun reading of "the"
+        let mut chart = Chart::new();
+        chart.add_position();
+        chart.add_position();
+        chart.add_edge(Edge::new(0, 1, "the", None, 0));
+        chart.add_edge(Edge::new(0, 1, "Det", Some(&rhs[2..3]), 1));
+        chart.add_edge(Edge::new(0, 1, "Noun", Some(&rhs[2..3]), 1));
+        chart.add_position();
+        chart.add_edge(Edge::new(1, 2, "lion", None, 0));
+        chart.add_edge(Edge::new(1, 2, "Noun", Some(&rhs[3..]), 1));
+        chart.add_edge(Edge::new(0, 2, "NP", Some(&rhs[..2]), 2));
+        chart
+    }
+
+    fn names<'e>(edges: impl IntoIterator<Item = &'e Edge<'e>>) -> Vec<String> {
+        edges.into_iter().map(|edge| edge.to_string()).collect()
+    }
+
+    #[test]
+    fn index_finds_edges_by_category_and_position() {
+        let rhs: Vec<String> = ["Det", "Noun", "the", "lion"].iter().map(|s| s.to_string()).collect();
+        let chart = np_chart(&rhs);
+        let index = chart.index();
+
+        assert_eq!(names(index.category("Noun")), vec!("[0-1: Noun --> the . ]", "[1-2: Noun --> lion . ]"));
+        assert_eq!(index.category("VP").count(), 0);
+        assert_eq!(index.starting_at(1).count(), 2);
+        assert_eq!(index.starting_at(5).count(), 0);
+        assert_eq!(index.ending_at(1).len(), 3);
+        assert_eq!(names(index.span(0, 2)), vec!("[0-2: NP --> Det Noun . ]"));
+        assert_eq!(names(chart.span(0, 2)), vec!("[0-2: NP --> Det Noun . ]"));
+        assert_eq!(chart.span(0, 7).count(), 0);
+    }
+
+    #[test]
+    fn maximal_constituents_are_not_inside_others() {
+        let rhs: Vec<String> = ["Det", "Noun", "the", "lion"].iter().map(|s| s.to_string()).collect();
+        let chart = np_chart(&rhs);
+
+        assert_eq!(names(chart.index().maximal_constituents()), vec!("[0-2: NP --> Det Noun . ]"));
+    }
 }
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use crate::domain::models::grammar::Grammar;
+
+/// Interned symbol, an index into a `SymbolTable`.
+pub type Symbol = usize;
+
+/// Index of a rule in the compiled (and source) grammar.
+pub type RuleId = usize;
+
+/// Maps symbol names to dense integer ids and back.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    ids: HashMap<String, Symbol>,
+    names: Vec<String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    /// Returns the id of `name`, adding it to the table if needed.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.ids.insert(name.to_string(), id);
+        self.names.push(name.to_string());
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        &self.names[symbol]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct CompiledRule {
+    lhs: Symbol,
+    rhs: Vec<Symbol>,
+    weight: f64,
+}
+
+impl CompiledRule {
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    pub fn lhs(&self) -> Symbol {
+        self.lhs
+    }
+
+    pub fn rhs(&self) -> &[Symbol] {
+        &self.rhs
+    }
+}
+
+/// A `Grammar` with every symbol interned.
+///
+/// Rule ids are the indices of the rules in the source grammar, so a
+/// `RuleId` can be used to look up the original `Rule`.
+#[derive(Debug)]
+pub struct CompiledGrammar {
+    symbols: SymbolTable,
+    rules: Vec<CompiledRule>,
+    leftcorners: Vec<Vec<RuleId>>,
+    nullable_leftcorners: Vec<Vec<(RuleId, usize)>>,
+    expansions: Vec<Vec<RuleId>>,
+    left_descendants: Vec<Vec<Symbol>>,
+    terminals: Vec<bool>,
+    preterminals: Vec<bool>,
+    nullable: Vec<bool>,
+    nullable_rules: Vec<RuleId>,
+}
+
+impl CompiledGrammar {
+    pub fn compile(grammar: &Grammar) -> Self {
+        let mut symbols = SymbolTable::new();
+        let rules: Vec<CompiledRule> = grammar.rules.iter()
+            .map(|rule| CompiledRule {
+                lhs: symbols.intern(rule.lhs()),
+                rhs: rule.rhs().iter().map(|sym| symbols.intern(sym)).collect(),
+                weight: rule.weight(),
+            })
+            .collect();
+        let nullable = Self::find_nullable(&rules, symbols.len());
+        let mut leftcorners = vec!(Vec::new(); symbols.len());
+        let mut nullable_leftcorners = vec!(Vec::new(); symbols.len());
+        let mut nullable_rules = Vec::new();
+        for (id, rule) in rules.iter().enumerate() {
+            if let Some(&first) = rule.rhs.first() {
+                leftcorners[first].push(id);
+            }
+            for (i, &sym) in rule.rhs.iter().enumerate().skip(1) {
+                if !nullable[rule.rhs[i - 1]] {
+                    break;
+                }
+                nullable_leftcorners[sym].push((id, i + 1));
+            }
+            if rule.rhs.iter().all(|&sym| nullable[sym]) {
+                nullable_rules.push(id);
+            }
+        }
+        let mut expansions = vec!(Vec::new(); symbols.len());
+        for (id, rule) in rules.iter().enumerate() {
+            expansions[rule.lhs].push(id);
+        }
+        let left_descendants = (0..symbols.len())
+            .map(|symbol| Self::find_left_descendants(&rules, &expansions, &nullable, symbol))
+            .collect();
+        let mut terminals = vec!(true; symbols.len());
+        for rule in &rules {
+            terminals[rule.lhs] = false;
+        }
+        let mut preterminals = vec!(false; symbols.len());
+        for rule in &rules {
+            if let [word] = rule.rhs[..] {
+                if terminals[word] {
+                    preterminals[rule.lhs] = true;
+                }
+            }
+        }
+        CompiledGrammar {
+            symbols,
+            rules,
+            leftcorners,
+            nullable_leftcorners,
+            expansions,
+            left_descendants,
+            terminals,
+            preterminals,
+            nullable,
+            nullable_rules,
+        }
+    }
+
+    /// The symbols that derive the empty string.
+    fn find_nullable(rules: &[CompiledRule], symbols: usize) -> Vec<bool> {
+        let mut nullable = vec!(false; symbols);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in rules {
+                if !nullable[rule.lhs] && rule.rhs.iter().all(|&sym| nullable[sym]) {
+                    nullable[rule.lhs] = true;
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+
+    fn find_left_descendants(
+        rules: &[CompiledRule],
+        expansions: &[Vec<RuleId>],
+        nullable: &[bool],
+        symbol: Symbol,
+    ) -> Vec<Symbol> {
+        let mut found = vec!(symbol);
+        let mut agenda = vec!(symbol);
+        while let Some(parent) = agenda.pop() {
+            for &rule in &expansions[parent] {
+                for &first in &rules[rule].rhs {
+                    if !found.contains(&first) {
+                        found.push(first);
+                        agenda.push(first);
+                    }
+                    if !nullable[first] {
+                        break;
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn rules(&self) -> &[CompiledRule] {
+        &self.rules
+    }
+
+    pub fn rule(&self, id: RuleId) -> &CompiledRule {
+        &self.rules[id]
+    }
+
+    /// The rules whose right-hand side starts with `symbol`.
+    pub fn leftcorners(&self, symbol: Symbol) -> &[RuleId] {
+        &self.leftcorners[symbol]
+    }
+
+    /// The rules where `symbol` comes after a non-empty sequence of
+    /// nullable symbols, with the position after `symbol`.
+    pub fn nullable_leftcorners(&self, symbol: Symbol) -> &[(RuleId, usize)] {
+        &self.nullable_leftcorners[symbol]
+    }
+
+    /// Whether `symbol` derives the empty string.
+    pub fn is_nullable(&self, symbol: Symbol) -> bool {
+        self.nullable[symbol]
+    }
+
+    /// The rules whose right-hand side derives the empty string.
+    pub fn nullable_rules(&self) -> &[RuleId] {
+        &self.nullable_rules
+    }
+
+    /// The rules with `symbol` as left-hand side.
+    pub fn expansions(&self, symbol: Symbol) -> &[RuleId] {
+        &self.expansions[symbol]
+    }
+
+    /// Whether `symbol` is a word, i.e. not the left-hand side of any rule.
+    pub fn is_terminal(&self, symbol: Symbol) -> bool {
+        self.terminals[symbol]
+    }
+
+    /// Whether `symbol` is a lexical category, with a rule rewriting it to
+    /// a single word.
+    pub fn is_preterminal(&self, symbol: Symbol) -> bool {
+        self.preterminals[symbol]
+    }
+
+    /// The symbols that can start a `symbol`, including `symbol` itself.
+    pub fn left_descendants(&self, symbol: Symbol) -> &[Symbol] {
+        &self.left_descendants[symbol]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::grammar::Rule;
+    use crate::parser::example_grammar;
+
+    #[test]
+    fn intern_returns_same_id_for_same_name() {
+        let mut symbols = SymbolTable::new();
+        let np = symbols.intern("NP");
+        let vp = symbols.intern("VP");
+
+        assert_ne!(np, vp);
+        assert_eq!(symbols.intern("NP"), np);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols.name(vp), "VP");
+        assert_eq!(symbols.get("PP"), None);
+    }
+
+    #[test]
+    fn compile_keeps_rule_order() {
+        let grammar = example_grammar();
+        let compiled = CompiledGrammar::compile(&grammar);
+        let symbols = compiled.symbols();
+
+        assert_eq!(compiled.rules().len(), grammar.rules.len());
+        for (rule, compiled_rule) in grammar.rules.iter().zip(compiled.rules()) {
+            assert_eq!(symbols.name(compiled_rule.lhs()), rule.lhs());
+            let rhs: Vec<&str> = compiled_rule.rhs().iter().map(|&s| symbols.name(s)).collect();
+            assert_eq!(rhs, rule.rhs());
+        }
+    }
+
+    #[test]
+    fn compile_builds_leftcorner_table() {
+        let compiled = CompiledGrammar::compile(&example_grammar());
+        let np = compiled.symbols().get("NP").unwrap();
+        let s = compiled.symbols().get("S").unwrap();
+
+        assert_eq!(compiled.leftcorners(np).len(), 2);
+        assert!(compiled.leftcorners(s).is_empty());
+        assert_eq!(compiled.expansions(np), &[4, 5]);
+        assert_eq!(compiled.expansions(s), &[0]);
+    }
+
+    #[test]
+    fn compile_classifies_terminals_and_preterminals() {
+        let compiled = CompiledGrammar::compile(&example_grammar());
+        let symbols = compiled.symbols();
+        let is_terminal = |name| compiled.is_terminal(symbols.get(name).unwrap());
+        let is_preterminal = |name| compiled.is_preterminal(symbols.get(name).unwrap());
+
+        assert!(is_terminal("lion"));
+        assert!(!is_terminal("Noun"));
+        assert!(!is_terminal("NP"));
+        assert!(is_preterminal("Noun"));
+        assert!(!is_preterminal("NP"));
+        assert!(!is_preterminal("lion"));
+    }
+
+    #[test]
+    fn compile_finds_left_descendants() {
+        let compiled = CompiledGrammar::compile(&example_grammar());
+        let symbols = compiled.symbols();
+        let mut names: Vec<&str> = compiled.left_descendants(symbols.get("S").unwrap())
+            .iter()
+            .map(|&s| symbols.name(s))
+            .collect();
+        names.sort();
+
+        assert_eq!(names, vec!("Det", "NP", "S", "a", "the"));
+        assert_eq!(compiled.left_descendants(symbols.get("lion").unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn nullable_symbols_and_their_leftcorners() {
+        let rule = |lhs: &str, rhs: &[&str]| Rule::new(lhs, rhs.iter().map(|s| s.to_string()).collect());
+        let grammar = Grammar::from_rules(vec!(
+            rule("NP", &["Det", "Adj", "Noun"]),
+            rule("Det", &[]),
+            rule("Det", &["the"]),
+            rule("Adj", &[]),
+            rule("Noun", &["lion"]),
+        ));
+        let compiled = CompiledGrammar::compile(&grammar);
+        let symbols = compiled.symbols();
+        let sym = |name| symbols.get(name).unwrap();
+
+        assert!(compiled.is_nullable(sym("Det")));
+        assert!(!compiled.is_nullable(sym("NP")));
+        assert_eq!(compiled.nullable_rules(), &[1, 3]);
+        assert_eq!(compiled.nullable_leftcorners(sym("Adj")), &[(0, 2)]);
+        assert_eq!(compiled.nullable_leftcorners(sym("Noun")), &[(0, 3)]);
+        assert!(compiled.left_descendants(sym("NP")).contains(&sym("lion")));
+    }
+}
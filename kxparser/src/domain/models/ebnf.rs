@@ -0,0 +1,396 @@
+//! EBNF operators in right-hand sides.
+//!
+//! A right-hand side with EBNF operators is a sequence of `Node`s: symbols,
+//! groups of alternatives, and nodes that are optional (`?`), repeated any
+//! number of times (`*`) or at least once (`+`). The readers build the
+//! nodes from their own syntax, as in `Det? Adj* Noun (PP | Rel)*`, so a
+//! symbol is never taken for an operator because of its spelling.
+//!
+//! Such rules are compiled into plain rules. Alternatives, groups and
+//! optional parts give one rule for each choice, while repetitions get
+//! auxiliary categories named after them with the reserved prefix `@`,
+//! like `@Adj+` and `@(Adv | PP)+`. `Grammar::validate` rejects any other
+//! category with the prefix, and `Tree::flatten` removes the auxiliary
+//! categories from a tree again.
+//!
+//! The weights of the rules compiled from a rule sum to its weight, so
+//! that probabilistic grammars stay normalized: both choices of an
+//! optional part get half of the weight, the alternatives of a group share
+//! it equally, and the two rules of a repetition, `@X+ --> X` and
+//! `@X+ --> @X+ X`, have weight 0.5 each.
+
+use std::fmt;
+
+use crate::domain::models::grammar::{Rule, NAMESPACE_SEPARATOR};
+use crate::error::{Error, Result};
+
+/// The prefix of the auxiliary categories.
+pub const AUXILIARY_PREFIX: char = '@';
+
+/// A part of a right-hand side.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Symbol(String),
+    /// Alternative sequences in parentheses.
+    Group(Vec<Vec<Node>>),
+    Optional(Box<Node>),
+    Star(Box<Node>),
+    Plus(Box<Node>),
+}
+
+/// The tokens of a right-hand side, as the readers find them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Symbol(String),
+    Open,
+    Close,
+    Bar,
+    /// `?`, `*` or `+` after a symbol or a group.
+    Operator(char),
+}
+
+/// Whether `symbol` is an auxiliary category made for an EBNF operator,
+/// possibly in a namespace: the prefix followed by a repetition written
+/// as `Node` formats it.
+pub fn is_auxiliary(symbol: &str) -> bool {
+    match reserved_name(symbol) {
+        Some(name) => match parse_str(name).as_deref() {
+            Ok([alternative]) => matches!(&alternative[..], [node @ Node::Plus(_)] if node.to_string() == name),
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+/// Whether `symbol` starts with the reserved prefix, possibly in a
+/// namespace.
+pub fn has_reserved_prefix(symbol: &str) -> bool {
+    reserved_name(symbol).is_some()
+}
+
+/// The part of `symbol` after the reserved prefix.
+fn reserved_name(symbol: &str) -> Option<&str> {
+    symbol.strip_prefix(AUXILIARY_PREFIX).or_else(|| {
+        symbol.split_once(&format!("{}{}", NAMESPACE_SEPARATOR, AUXILIARY_PREFIX)).map(|(_, name)| name)
+    })
+}
+
+/// Parses the alternatives of a right-hand side from `tokens` with their
+/// positions, where `end` is the position after the last token. Errors
+/// are made by `error` from a position and a message.
+pub fn parse<P: Copy>(tokens: &[(P, Token)], end: P, error: impl Fn(P, &str) -> Error) -> Result<Vec<Vec<Node>>> {
+    let mut i = 0;
+    let alternatives = parse_alternatives(tokens, &mut i, end, &error)?;
+    match tokens.get(i) {
+        Some((position, _)) => Err(error(*position, "unbalanced ')'")),
+        None => Ok(alternatives),
+    }
+}
+
+/// Parses a right-hand side written as in the text format of `io::text`,
+/// `Det? Adj* "C++" (PP | Rel)*`, where a symbol in double quotes, with
+/// `\"` and `\\` for quotes and backslashes, is taken literally.
+pub fn parse_str(text: &str) -> Result<Vec<Vec<Node>>> {
+    let error = |column: usize, message: &str| Error::Format {
+        file: None,
+        position: None,
+        message: format!("{} at column {} of '{}'", message, column, text),
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '(' | ')' | '|' | '?' | '*' | '+' => {
+                tokens.push((column, match chars[i] {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    '|' => Token::Bar,
+                    op => Token::Operator(op),
+                }));
+                i += 1;
+            }
+            '"' => {
+                let mut symbol = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(error(column, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                            symbol.push(chars[i + 1]);
+                            i += 1;
+                        }
+                        Some('\\') => return Err(error(i + 1, "unknown escape")),
+                        Some(&c) => symbol.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((column, Token::Symbol(symbol)));
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !"()|\"".contains(chars[i]) {
+                    if chars[i] == '[' {
+                        i += chars[i..].iter().position(|&c| c == ']').ok_or_else(|| error(i + 1, "missing ']'"))?;
+                    }
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                tokens.extend(split_operators(&word, column));
+            }
+        }
+    }
+    parse(&tokens, chars.len() + 1, error)
+}
+
+/// The tokens of a bare word starting in `column`: a symbol and the
+/// operators it ends with.
+pub fn split_operators(word: &str, column: usize) -> Vec<(usize, Token)> {
+    let symbol = word.trim_end_matches(['?', '*', '+']);
+    let mut tokens = Vec::new();
+    if !symbol.is_empty() {
+        tokens.push((column, Token::Symbol(symbol.to_string())));
+    }
+    let offset = symbol.chars().count();
+    tokens.extend(word[symbol.len()..].chars().enumerate().map(|(i, op)| (column + offset + i, Token::Operator(op))));
+    tokens
+}
+
+/// Parses alternatives up to the end of the tokens, or up to a closing
+/// parenthesis, which is left at `i`.
+fn parse_alternatives<P: Copy>(
+    tokens: &[(P, Token)],
+    i: &mut usize,
+    end: P,
+    error: &impl Fn(P, &str) -> Error,
+) -> Result<Vec<Vec<Node>>> {
+    let mut alternatives = vec!(Vec::new());
+    while let Some((position, token)) = tokens.get(*i) {
+        let position = *position;
+        let node = match token {
+            Token::Close => break,
+            Token::Bar => {
+                if alternatives.last().is_some_and(Vec::is_empty) {
+                    return Err(error(position, "empty alternative"));
+                }
+                alternatives.push(Vec::new());
+                *i += 1;
+                continue;
+            }
+            Token::Symbol(symbol) => Node::Symbol(symbol.clone()),
+            Token::Open => {
+                *i += 1;
+                let mut group = parse_alternatives(tokens, i, end, error)?;
+                if tokens.get(*i).is_none() {
+                    return Err(error(end, "missing ')'"));
+                }
+                if group.len() == 1 && group[0].len() == 1 {
+                    group.pop().unwrap().pop().unwrap()
+                } else {
+                    Node::Group(group)
+                }
+            }
+            Token::Operator(op) => {
+                let operand = alternatives.last_mut().unwrap().pop()
+                    .ok_or_else(|| error(position, &format!("'{}' without an operand", op)))?;
+                match op {
+                    '?' => Node::Optional(Box::new(operand)),
+                    '*' => Node::Star(Box::new(operand)),
+                    '+' => Node::Plus(Box::new(operand)),
+                    _ => return Err(error(position, &format!("unknown operator '{}'", op))),
+                }
+            }
+        };
+        alternatives.last_mut().unwrap().push(node);
+        *i += 1;
+    }
+    if alternatives.len() > 1 && alternatives.last().is_some_and(Vec::is_empty) {
+        let position = tokens.get(*i).map_or(end, |(position, _)| *position);
+        return Err(error(position, "empty alternative"));
+    }
+    Ok(alternatives)
+}
+
+/// Compiles the rule `lhs --> rhs` with `weight` into plain rules, adding
+/// the auxiliary rules it needs to `auxiliary` unless they are there
+/// already.
+pub fn compile(lhs: &str, rhs: &[Node], weight: f64, auxiliary: &mut Vec<Rule>) -> Vec<Rule> {
+    expand_sequence(rhs, auxiliary).into_iter()
+        .map(|(rhs, share)| Rule::new(lhs, rhs).with_weight(weight * share))
+        .collect()
+}
+
+/// The right-hand sides for a sequence of nodes, with their share of the
+/// weight.
+fn expand_sequence(nodes: &[Node], auxiliary: &mut Vec<Rule>) -> Vec<(Vec<String>, f64)> {
+    let mut result = vec!((Vec::new(), 1.0));
+    for node in nodes {
+        let choices = expand(node, auxiliary);
+        result = result.iter()
+            .flat_map(|(prefix, p)| choices.iter().map(move |(choice, q)| ([prefix.clone(), choice.clone()].concat(), p * q)))
+            .collect();
+    }
+    result
+}
+
+/// The ways to write `node` as a sequence of symbols, with their share of
+/// the weight.
+fn expand(node: &Node, auxiliary: &mut Vec<Rule>) -> Vec<(Vec<String>, f64)> {
+    match node {
+        Node::Symbol(symbol) => vec!((vec!(symbol.clone()), 1.0)),
+        Node::Group(alternatives) => {
+            let share = 1.0 / alternatives.len() as f64;
+            let mut choices = Vec::new();
+            for alternative in alternatives {
+                choices.extend(expand_sequence(alternative, auxiliary).into_iter().map(|(rhs, p)| (rhs, p * share)));
+            }
+            choices
+        }
+        Node::Optional(inner) => {
+            let mut choices = vec!((Vec::new(), 0.5));
+            choices.extend(expand(inner, auxiliary).into_iter().map(|(rhs, p)| (rhs, p * 0.5)));
+            choices
+        }
+        Node::Star(inner) => expand(&Node::Optional(Box::new(Node::Plus(inner.clone()))), auxiliary),
+        Node::Plus(inner) => {
+            let name = format!("{}{}", AUXILIARY_PREFIX, node);
+            if !auxiliary.iter().any(|rule| rule.lhs() == name) {
+                for (rhs, p) in expand(inner, auxiliary) {
+                    auxiliary.push(Rule::new(&name, rhs.clone()).with_weight(p * 0.5));
+                    auxiliary.push(Rule::new(&name, [vec!(name.clone()), rhs].concat()).with_weight(p * 0.5));
+                }
+            }
+            vec!((vec!(name), 1.0))
+        }
+    }
+}
+
+/// Formats the node as in `parse_str`, which makes the names of the
+/// auxiliary categories tell different nodes apart.
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Symbol(symbol) if symbol.is_empty()
+                || symbol.ends_with(['?', '*', '+'])
+                || symbol.contains(|c: char| c.is_whitespace() || "()|\"\\".contains(c)) => {
+                write!(f, "\"{}\"", symbol.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            Node::Symbol(symbol) => write!(f, "{}", symbol),
+            Node::Group(alternatives) => {
+                let alternatives: Vec<String> = alternatives.iter()
+                    .map(|nodes| nodes.iter().map(Node::to_string).collect::<Vec<_>>().join(" "))
+                    .collect();
+                write!(f, "({})", alternatives.join(" | "))
+            }
+            Node::Optional(inner) => write!(f, "{}?", inner),
+            Node::Star(inner) => write!(f, "{}*", inner),
+            Node::Plus(inner) => write!(f, "{}+", inner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compiled(rhs: &str) -> (Vec<String>, Vec<String>) {
+        let mut auxiliary = Vec::new();
+        let mut rules = Vec::new();
+        for alternative in parse_str(rhs).unwrap() {
+            rules.extend(compile("NP", &alternative, 1.0, &mut auxiliary));
+        }
+        (
+            rules.iter().map(Rule::to_string).collect(),
+            auxiliary.iter().map(Rule::to_string).collect(),
+        )
+    }
+
+    #[test]
+    fn optional_symbol_gives_two_rules() {
+        let (rules, auxiliary) = compiled("Det? Noun");
+
+        assert_eq!(rules, vec!("NP --> Noun [0.5]", "NP --> Det Noun [0.5]"));
+        assert!(auxiliary.is_empty());
+    }
+
+    #[test]
+    fn repetition_gets_an_auxiliary_category() {
+        let (rules, auxiliary) = compiled("Det Adj* Noun");
+
+        assert_eq!(rules, vec!("NP --> Det Noun [0.5]", "NP --> Det @Adj+ Noun [0.5]"));
+        assert_eq!(auxiliary, vec!("@Adj+ --> Adj [0.5]", "@Adj+ --> @Adj+ Adj [0.5]"));
+    }
+
+    #[test]
+    fn groups_and_alternatives() {
+        let (rules, auxiliary) = compiled("Det Noun ( PP | RelClause )+ | (Name)");
+
+        assert_eq!(rules, vec!("NP --> Det Noun @(PP | RelClause)+", "NP --> Name"));
+        assert_eq!(auxiliary, vec!(
+            "@(PP | RelClause)+ --> PP [0.25]",
+            "@(PP | RelClause)+ --> @(PP | RelClause)+ PP [0.25]",
+            "@(PP | RelClause)+ --> RelClause [0.25]",
+            "@(PP | RelClause)+ --> @(PP | RelClause)+ RelClause [0.25]",
+        ));
+    }
+
+    #[test]
+    fn weights_keep_their_sum_per_category() {
+        let mut auxiliary = Vec::new();
+        let mut rules = Vec::new();
+        for alternative in parse_str("Det? Adj+ N (PP | Rel | Adv)*").unwrap() {
+            rules.extend(compile("NP", &alternative, 0.5, &mut auxiliary));
+        }
+        let total = |rules: &[Rule], lhs: &str| -> f64 {
+            rules.iter().filter(|rule| rule.lhs() == lhs).map(Rule::weight).sum()
+        };
+
+        assert_eq!(rules.len(), 4);
+        assert!((total(&rules, "NP") - 0.5).abs() < 1e-12);
+        assert!((total(&auxiliary, "@Adj+") - 1.0).abs() < 1e-12);
+        assert!((total(&auxiliary, "@(PP | Rel | Adv)+") - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn quoted_symbols_are_literal() {
+        let (rules, auxiliary) = compiled("\"(\" \"what?\" \"C++\"+ \")\"");
+
+        assert_eq!(rules, vec!("NP --> ( what? @\"C++\"+ )"));
+        assert_eq!(auxiliary, vec!("@\"C++\"+ --> C++ [0.5]", "@\"C++\"+ --> @\"C++\"+ C++ [0.5]"));
+        assert_eq!(compiled("NP[num=?n] Adj[deg=?d]*").0[1], "NP --> NP[num=?n] @Adj[deg=?d]+ [0.5]");
+    }
+
+    #[test]
+    fn plain_rules_are_unchanged() {
+        let plain = Rule::new("NP", vec!("Det".to_string(), "Noun".to_string()));
+
+        assert_eq!(compile("NP", &parse_str("Det Noun").unwrap()[0], 1.0, &mut Vec::new()), vec!(plain));
+    }
+
+    #[test]
+    fn malformed_rules_are_errors() {
+        let message = |rhs: &str| parse_str(rhs).unwrap_err().to_string();
+
+        assert_eq!(message("( Det Noun"), "missing ')' at column 11 of '( Det Noun'");
+        assert_eq!(message("Det )"), "unbalanced ')' at column 5 of 'Det )'");
+        assert_eq!(message("Det | | Noun"), "empty alternative at column 7 of 'Det | | Noun'");
+        assert_eq!(message("* Det"), "'*' without an operand at column 1 of '* Det'");
+        assert_eq!(message("( Det | )"), "empty alternative at column 9 of '( Det | )'");
+    }
+
+    #[test]
+    fn auxiliary_categories_are_recognizable() {
+        assert!(is_auxiliary("@Adj+"));
+        assert!(is_auxiliary("np::@(PP | RelClause)+"));
+        assert!(is_auxiliary("@\"C++\"+"));
+        assert!(!is_auxiliary("Adj+"));
+        assert!(!is_auxiliary("@Foo") && has_reserved_prefix("@Foo"));
+        assert!(!is_auxiliary("@Adj?") && !is_auxiliary("@(PP|RelClause)+"));
+        assert!(!has_reserved_prefix("e@mail"));
+        assert!(!is_auxiliary("(PP | RelClause)"));
+    }
+}
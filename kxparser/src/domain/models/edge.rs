@@ -3,31 +3,82 @@ use std::{
     fmt,
 };
 
-#[derive(Debug, Eq, Hash, PartialEq, Clone)]
+use crate::domain::models::grammar::Rule;
+
+/// An edge in the chart.
+///
+/// The right-hand side is borrowed from the grammar, so edges are cheap
+/// to copy and creating one never allocates.
+#[derive(Debug, Eq, Hash, PartialEq, Clone, Copy)]
 pub struct Edge<'a> {
     start: usize,
     end: usize,
     lhs: &'a str,
-    rhs: Vec<&'a str>,
+    rhs: &'a [String],
     dot: usize,
 }
 
 impl<'a> Edge<'a> {
-    pub fn new(start: usize, end: usize, lhs: &'a str, rhs: Option<&[&'a str]>, dot: usize) -> Self {
+    pub fn new(start: usize, end: usize, lhs: &'a str, rhs: Option<&'a [String]>, dot: usize) -> Self {
         Edge::<'a> {
-            start: start,
-            end: end,
-            lhs: lhs,
-            rhs: match rhs {
-                None => Vec::new(),
-                Some(vec) => vec.iter().map(|x| *x).collect()
-            },
-            dot: dot,
+            start,
+            end,
+            lhs,
+            rhs: rhs.unwrap_or(&[]),
+            dot,
+        }
+    }
+
+    /// Creates an edge for `rule` spanning `start`-`end` with the dot at `dot`.
+    pub fn from_rule(start: usize, end: usize, rule: &'a Rule, dot: usize) -> Self {
+        Edge {
+            start,
+            end,
+            lhs: rule.lhs(),
+            rhs: rule.rhs(),
+            dot,
+        }
+    }
+
+    /// Returns a copy of this edge with the dot moved one step, now ending in `end`.
+    pub fn advance(&self, end: usize) -> Self {
+        Edge {
+            start: self.start,
+            end,
+            lhs: self.lhs,
+            rhs: self.rhs,
+            dot: self.dot + 1,
         }
     }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn lhs(&self) -> &'a str {
+        self.lhs
+    }
+
+    pub fn rhs(&self) -> &'a [String] {
+        self.rhs
+    }
+
+    pub fn dot(&self) -> usize {
+        self.dot
+    }
+
     pub fn is_passive(&self) -> bool {
         self.dot == self.rhs.len()
     }
+
+    /// The symbol after the dot, or `None` if the edge is passive.
+    pub fn next_symbol(&self) -> Option<&'a str> {
+        self.rhs.get(self.dot).map(String::as_str)
+    }
 }
 
 impl fmt::Display for Edge<'_> {
@@ -62,10 +113,14 @@ impl PartialOrd for Edge<'_> {
 mod tests {
     use super::*;
 
+    fn np_vp() -> Vec<String> {
+        vec!(String::from("NP"), String::from("VP"))
+    }
+
     #[test]
     fn new_with_rhs_creates_edge() {
         let lhs = "S";
-        let rhs = vec!("NP", "VP");
+        let rhs = np_vp();
         let edge = Edge::new(0, 2, lhs, Some(&rhs), 1);
 
         assert_eq!(edge.start, 0);
@@ -82,27 +137,28 @@ mod tests {
         assert_eq!(edge.start, 0);
         assert_eq!(edge.end, 2);
         assert_eq!(edge.lhs, "S");
-        assert_eq!(edge.rhs, Vec::<&str>::new());
+        assert_eq!(edge.rhs, Vec::<String>::new());
         assert_eq!(edge.dot, 1);
     }
 
     #[test]
     fn format_edge_with_rhs_and_dot_less_than_rhs_len() {
-        let edge = Edge::new(0, 2, "S", Some(&vec!("NP", "VP")), 1);
+        let rhs = np_vp();
+        let edge = Edge::new(0, 2, "S", Some(&rhs), 1);
 
         assert_eq!(format!("{}", edge), "[0-2: S --> NP . VP]");
     }
 
     #[test]
     fn format_edge_with_rhs_and_dot_equal_to_rhs_len() {
-        let edge = Edge::new(0, 2, "S", Some(&vec!("NP", "VP")), 2);
+        let rhs = np_vp();
+        let edge = Edge::new(0, 2, "S", Some(&rhs), 2);
 
         assert_eq!(format!("{}", edge), "[0-2: S --> NP VP . ]");
     }
 
     #[test]
     fn format_edge_without_rhs() {
-        let lhs = "S";
         let edge = Edge::new(0, 2, "S", None, 0);
 
         assert_eq!(format!("{}", edge), "[0-2: S -->  . ]");
@@ -110,14 +166,16 @@ mod tests {
 
     #[test]
     fn edge_with_dot_less_than_rhs_len_is_not_passive() {
-        let edge = Edge::new(0, 2, "S", Some(&vec!("NP", "VP")), 1);
+        let rhs = np_vp();
+        let edge = Edge::new(0, 2, "S", Some(&rhs), 1);
 
         assert!(!edge.is_passive());
     }
 
     #[test]
     fn edge_with_dot_equal_to_rhs_len_is_passive() {
-        let edge = Edge::new(0, 2, "S", Some(&vec!("NP", "VP")), 2);
+        let rhs = np_vp();
+        let edge = Edge::new(0, 2, "S", Some(&rhs), 2);
 
         assert!(edge.is_passive());
     }
@@ -129,6 +187,45 @@ mod tests {
         assert!(edge.is_passive());    
     }
 
+    #[test]
+    fn from_rule_creates_edge() {
+        let rule = Rule::new("S", np_vp());
+        let edge = Edge::from_rule(0, 1, &rule, 1);
+        let rhs = np_vp();
+
+        assert_eq!(edge, Edge::new(0, 1, "S", Some(&rhs), 1));
+    }
+
+    #[test]
+    fn advance_moves_dot_and_end() {
+        let rhs = np_vp();
+        let edge = Edge::new(0, 2, "S", Some(&rhs), 1);
+        let next = edge.advance(5);
+
+        assert_eq!(next.start(), 0);
+        assert_eq!(next.end(), 5);
+        assert_eq!(next.lhs(), "S");
+        assert_eq!(next.rhs(), &["NP", "VP"]);
+        assert_eq!(next.dot(), 2);
+        assert!(next.is_passive());
+    }
+
+    #[test]
+    fn next_symbol_of_active_edge() {
+        let rhs = np_vp();
+        let edge = Edge::new(0, 2, "S", Some(&rhs), 1);
+
+        assert_eq!(edge.next_symbol(), Some("VP"));
+    }
+
+    #[test]
+    fn next_symbol_of_passive_edge() {
+        let rhs = np_vp();
+        let edge = Edge::new(0, 2, "S", Some(&rhs), 2);
+
+        assert_eq!(edge.next_symbol(), None);
+    }
+
     #[test]
     fn test_cmp() {
         let e1 = Edge::new(0, 2, "D", None, 0);
@@ -0,0 +1,140 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::Error;
+
+/// The value of a feature: a constant, or a variable shared between the
+/// categories of a rule.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Value {
+    Atom(String),
+    Var(String),
+}
+
+/// A category with a flat feature structure, written `NP[num=?n,per=3]`.
+///
+/// Feature names are unique and kept sorted. Variables start with `?`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Category {
+    name: String,
+    features: Vec<(String, Value)>,
+}
+
+impl Category {
+    pub fn new(name: &str) -> Self {
+        Category { name: name.to_string(), features: Vec::new() }
+    }
+
+    /// Sets `feature` to `value`, replacing any previous value.
+    pub fn with_feature(mut self, feature: &str, value: Value) -> Self {
+        match self.features.binary_search_by(|(name, _)| name.as_str().cmp(feature)) {
+            Ok(i) => self.features[i].1 = value,
+            Err(i) => self.features.insert(i, (feature.to_string(), value)),
+        }
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn features(&self) -> &[(String, Value)] {
+        &self.features
+    }
+
+    pub fn get(&self, feature: &str) -> Option<&Value> {
+        self.features.iter().find(|(name, _)| name == feature).map(|(_, value)| value)
+    }
+}
+
+impl FromStr for Category {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let error = |message: &str| Error::Format {
+            file: None,
+            position: None,
+            message: format!("{} in category '{}'", message, s),
+        };
+        let s = s.trim();
+        let (name, features) = match s.find('[') {
+            None => (s, None),
+            Some(open) => {
+                let features = s[open + 1..].strip_suffix(']').ok_or_else(|| error("missing ']'"))?;
+                (s[..open].trim(), Some(features))
+            }
+        };
+        if name.is_empty() {
+            return Err(error("missing name"));
+        }
+        let mut category = Category::new(name);
+        for pair in features.into_iter().flat_map(|features| features.split(',')) {
+            let (feature, value) = pair.split_once('=').ok_or_else(|| error("expected feature=value"))?;
+            let (feature, value) = (feature.trim(), value.trim());
+            if feature.is_empty() || value.is_empty() || value == "?" {
+                return Err(error("expected feature=value"));
+            }
+            if category.get(feature).is_some() {
+                return Err(error("repeated feature"));
+            }
+            let value = match value.strip_prefix('?') {
+                Some(var) => Value::Var(var.to_string()),
+                None => Value::Atom(value.to_string()),
+            };
+            category = category.with_feature(feature, value);
+        }
+        Ok(category)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Atom(atom) => write!(f, "{}", atom),
+            Value::Var(var) => write!(f, "?{}", var),
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.features.is_empty() {
+            let features: Vec<String> = self.features.iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            write!(f, "[{}]", features.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_category_with_features() {
+        let np: Category = "NP[per=3, num=?n]".parse().unwrap();
+
+        assert_eq!(np.name(), "NP");
+        assert_eq!(np.get("num"), Some(&Value::Var("n".to_string())));
+        assert_eq!(np.get("per"), Some(&Value::Atom("3".to_string())));
+        assert_eq!(np.get("case"), None);
+        assert_eq!(np.to_string(), "NP[num=?n,per=3]");
+    }
+
+    #[test]
+    fn parse_plain_category() {
+        let s: Category = "S".parse().unwrap();
+
+        assert_eq!(s, Category::new("S"));
+        assert_eq!(s.to_string(), "S");
+    }
+
+    #[test]
+    fn parse_rejects_malformed_features() {
+        for bad in &["NP[num=sg", "NP[num]", "NP[num=?]", "[num=sg]", "NP[num=sg,num=pl]"] {
+            assert!(bad.parse::<Category>().is_err(), "{}", bad);
+        }
+    }
+}
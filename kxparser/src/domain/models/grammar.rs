@@ -1,24 +1,385 @@
-use std::fmt;
-use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+};
+use serde::{
+    de::{
+        self,
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+        IntoDeserializer,
+    },
+    Deserialize, Deserializer, Serialize,
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::domain::models::ebnf;
+use crate::error::{GrammarError, Result};
+
+/// A grammar: its rules, the start category and some descriptive
+/// metadata.
+///
+/// Without an explicit start category, the left-hand side of the first
+/// rule is the start category.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct Grammar {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
     pub rules: Vec<Rule>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// A grammar rule `lhs --> rhs`.
+///
+/// The weight defaults to 1. In a probabilistic grammar it is the
+/// probability of rewriting `lhs` with this rule.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Rule {
     lhs: String,
     rhs: Vec<String>,
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    weight: f64,
+}
+
+/// A rule schema, standing for a rule for each assignment of values to
+/// its variables.
+///
+/// `X --> X Conj X` with `X` in `NP`, `VP` and `S` is written
+/// `(lhs: "X", rhs: ["X", "Conj", "X"], vars: {"X": ["NP", "VP", "S"]})`
+/// in a grammar file. Variables are symbols of the rule, replaced as a
+/// whole, and several variables take all combinations of their values.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Schema {
+    lhs: String,
+    rhs: Vec<String>,
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    weight: f64,
+    vars: BTreeMap<String, Vec<String>>,
+}
+
+/// A rule with EBNF operators, written as in the text format:
+/// `(lhs: "NP", ebnf: "Det? Adj* Noun (PP | Rel)*")`, see `ebnf`.
+///
+/// Alternatives at the top level are separate rules with the same weight.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EbnfRule {
+    lhs: String,
+    ebnf: String,
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    weight: f64,
+}
+
+/// The inclusion of another grammar file, `(include: "lexicon.ron")`,
+/// or `(include: "lexicon.ron", namespace: "lex")` to prefix its
+/// categories as in `Grammar::with_namespace`.
+///
+/// The file name is relative to the directory of the including file.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Include {
+    pub include: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+/// An entry of a grammar file: a rule, a schema, an EBNF rule or an
+/// include.
+///
+/// The kind of entry follows from its fields: `include` makes an include,
+/// `vars` a schema, `ebnf` an EBNF rule and `rhs` a rule. Unknown and
+/// missing fields are errors.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged, try_from = "RawEntry")]
+pub enum Entry {
+    Schema(Schema),
+    Rule(Rule),
+    Ebnf(EbnfRule),
+    Include(Include),
+}
+
+/// The contents of a RON or JSON grammar file: a list of entries, or the
+/// entries with the metadata of the grammar, as in
+/// `(name: "English", start: "S", rules: [...])`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum GrammarFile {
+    Entries(Vec<Entry>),
+    Document {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start: Option<String>,
+        rules: Vec<Entry>,
+    },
+}
+
+/// The fields of any entry, before `Entry` tells them apart.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawEntry {
+    #[serde(default, deserialize_with = "present")]
+    lhs: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    rhs: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "present")]
+    ebnf: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    weight: Option<f64>,
+    #[serde(default, deserialize_with = "present")]
+    vars: Option<BTreeMap<String, Vec<String>>>,
+    #[serde(default, deserialize_with = "present")]
+    include: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    namespace: Option<String>,
+}
+
+/// The document form of `GrammarFile`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    #[serde(default, deserialize_with = "present")]
+    name: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    version: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    description: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    start: Option<String>,
+    rules: Vec<Entry>,
+}
+
+/// Reads an optional field that is present, in RON written with or
+/// without `Some`.
+fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> std::result::Result<Option<T>, D::Error> {
+    struct Visitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> de::Visitor<'de> for Visitor<T> {
+        type Value = Option<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a value")
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Option<T>, D::Error> {
+            T::deserialize(deserializer).map(Some)
+        }
+
+        fn visit_none<E: de::Error>(self) -> std::result::Result<Option<T>, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> std::result::Result<Option<T>, E> {
+            Ok(None)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Option<T>, E> {
+            T::deserialize(v.into_deserializer()).map(Some)
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<Option<T>, E> {
+            T::deserialize(v.into_deserializer()).map(Some)
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Option<T>, E> {
+            T::deserialize(v.into_deserializer()).map(Some)
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Option<T>, E> {
+            T::deserialize(v.into_deserializer()).map(Some)
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> std::result::Result<Option<T>, A::Error> {
+            T::deserialize(SeqAccessDeserializer::new(seq)).map(Some)
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> std::result::Result<Option<T>, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(Some)
+        }
+    }
+
+    deserializer.deserialize_any(Visitor(std::marker::PhantomData))
+}
+
+/// Separates a namespace from the name of a category, as in `lex::Noun`.
+pub const NAMESPACE_SEPARATOR: &str = "::";
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn is_default_weight(weight: &f64) -> bool {
+    *weight == default_weight()
 }
 
 impl Grammar {
     pub fn new() -> Self {
-        Grammar { rules: Vec::new() }
+        Grammar::default()
     }
 
     pub fn from_rules(rules: Vec<Rule>) -> Self {
-        Grammar { rules }
+        Grammar { rules, ..Grammar::default() }
+    }
+
+    pub fn with_start(mut self, start: &str) -> Self {
+        self.start = Some(start.to_string());
+        self
+    }
+
+    /// The start category, by default the left-hand side of the first
+    /// rule, or `None` for a grammar without rules.
+    pub fn start(&self) -> Option<&str> {
+        self.start.as_deref().or_else(|| self.rules.first().map(Rule::lhs))
+    }
+
+    /// Builds a grammar from rules, schemas and EBNF rules, expanding
+    /// the schemas in place and compiling the EBNF rules, whose auxiliary
+    /// rules come after all others. Includes are left out, they are read
+    /// by `io`.
+    pub fn from_entries(entries: Vec<Entry>) -> Result<Self> {
+        let mut rules = Vec::new();
+        let mut auxiliary = Vec::new();
+        for entry in entries {
+            match entry {
+                Entry::Rule(rule) => rules.push(rule),
+                Entry::Schema(schema) => rules.extend(schema.expand()),
+                Entry::Ebnf(rule) => rules.extend(rule.compile(&mut auxiliary)?),
+                Entry::Include(_) => (),
+            }
+        }
+        rules.extend(auxiliary);
+        Ok(Grammar::from_rules(rules))
+    }
+
+    /// Prefixes the categories, i.e. the symbols with rules, with
+    /// `namespace` and `::`. Other symbols are words, or categories of
+    /// the grammar that includes this one, and are kept as they are. So is
+    /// the metadata, except for the start category.
+    pub fn with_namespace(self, namespace: &str) -> Self {
+        let categories: HashSet<String> = self.rules.iter().map(|rule| rule.lhs.clone()).collect();
+        let prefix = |sym: String| if categories.contains(&sym) {
+            format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, sym)
+        } else {
+            sym
+        };
+        let rules = self.rules.into_iter()
+            .map(|rule| Rule {
+                lhs: prefix(rule.lhs),
+                rhs: rule.rhs.into_iter().map(prefix).collect(),
+                weight: rule.weight,
+            })
+            .collect();
+        Grammar { start: self.start.map(prefix), rules, ..self }
+    }
+
+    /// Checks that every rule has a category and a finite, non-negative
+    /// weight, and that there are rules for the start category.
+    pub fn validate(&self) -> std::result::Result<(), GrammarError> {
+        if let Some(start) = &self.start {
+            if !self.rules.iter().any(|rule| rule.lhs == *start) {
+                return Err(GrammarError::UnknownStart { start: start.clone() });
+            }
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.lhs.is_empty() {
+                return Err(GrammarError::EmptyLhs { rule: i });
+            }
+            if ebnf::has_reserved_prefix(&rule.lhs) && !ebnf::is_auxiliary(&rule.lhs) {
+                return Err(GrammarError::ReservedPrefix { rule: i, lhs: rule.lhs.clone() });
+            }
+            if !rule.weight.is_finite() || rule.weight < 0.0 {
+                return Err(GrammarError::InvalidWeight { rule: i, weight: rule.weight });
+            }
+        }
+        Ok(())
+    }
+
+    /// Scales the weights so that the rules for each category sum to 1.
+    pub fn normalize_weights(&mut self) {
+        let mut totals = HashMap::new();
+        for rule in &self.rules {
+            *totals.entry(rule.lhs.clone()).or_insert(0.0) += rule.weight;
+        }
+        for rule in &mut self.rules {
+            let total = totals[&rule.lhs];
+            if total > 0.0 {
+                rule.weight /= total;
+            }
+        }
+    }
+}
+
+impl GrammarFile {
+    /// The metadata, as a grammar without rules, and the entries.
+    pub fn into_parts(self) -> (Grammar, Vec<Entry>) {
+        match self {
+            GrammarFile::Entries(entries) => (Grammar::new(), entries),
+            GrammarFile::Document { name, version, description, start, rules } => {
+                (Grammar { name, version, description, start, rules: Vec::new() }, rules)
+            }
+        }
+    }
+}
+
+impl TryFrom<RawEntry> for Entry {
+    type Error = String;
+
+    fn try_from(raw: RawEntry) -> std::result::Result<Self, String> {
+        let weight = raw.weight.unwrap_or_else(default_weight);
+        match raw {
+            RawEntry { include: Some(include), namespace, lhs: None, rhs: None, ebnf: None, weight: None, vars: None } => {
+                Ok(Entry::Include(Include { include, namespace }))
+            }
+            RawEntry { include: Some(_), .. } => Err("an include has only the fields `include` and `namespace`".to_string()),
+            RawEntry { namespace: Some(_), .. } => Err("`namespace` without `include`".to_string()),
+            RawEntry { lhs: None, .. } => Err("missing field `lhs`".to_string()),
+            RawEntry { rhs: Some(_), ebnf: Some(_), .. } => Err("a rule has either `rhs` or `ebnf`".to_string()),
+            RawEntry { lhs: Some(lhs), rhs: Some(rhs), vars: Some(vars), .. } => {
+                Ok(Entry::Schema(Schema { lhs, rhs, weight, vars }))
+            }
+            RawEntry { vars: Some(_), .. } => Err("missing field `rhs` of a schema".to_string()),
+            RawEntry { lhs: Some(lhs), rhs: Some(rhs), .. } => Ok(Entry::Rule(Rule { lhs, rhs, weight })),
+            RawEntry { lhs: Some(lhs), ebnf: Some(ebnf), .. } => Ok(Entry::Ebnf(EbnfRule { lhs, ebnf, weight })),
+            RawEntry { .. } => Err("missing field `rhs` or `ebnf`".to_string()),
+        }
+    }
+}
+
+/// Reads a list of entries or a document, keeping the errors of the
+/// entries, where an untagged enum would only tell that nothing matched.
+impl<'de> Deserialize<'de> for GrammarFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = GrammarFile;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a list of rules or a grammar with metadata and rules")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<GrammarFile, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = seq.next_element()? {
+                    entries.push(entry);
+                }
+                Ok(GrammarFile::Entries(entries))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> std::result::Result<GrammarFile, A::Error> {
+                let Document { name, version, description, start, rules } =
+                    Document::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(GrammarFile::Document { name, version, description, start, rules })
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
     }
 }
 
@@ -26,29 +387,105 @@ impl Rule {
     pub fn new(lhs: &str, rhs: Vec<String>) -> Self {
         Rule {
             lhs: lhs.to_string(),
-            rhs: rhs,
+            rhs,
+            weight: default_weight(),
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn lhs(&self) -> &str {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &[String] {
+        &self.rhs
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+}
+
+impl Schema {
+    pub fn new(lhs: &str, rhs: Vec<String>, vars: BTreeMap<String, Vec<String>>) -> Self {
+        Schema { lhs: lhs.to_string(), rhs, weight: default_weight(), vars }
+    }
+
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// The rules for every assignment of values to the variables, the
+    /// first variable varying slowest.
+    pub fn expand(&self) -> Vec<Rule> {
+        let mut assignments: Vec<Vec<(&str, &str)>> = vec!(Vec::new());
+        for (var, values) in &self.vars {
+            assignments = assignments.iter()
+                .flat_map(|assignment| values.iter().map(move |value| {
+                    let mut assignment = assignment.clone();
+                    assignment.push((var.as_str(), value.as_str()));
+                    assignment
+                }))
+                .collect();
+        }
+        assignments.iter()
+            .map(|assignment| {
+                let substitute = |sym: &String| assignment.iter()
+                    .find(|(var, _)| var == sym)
+                    .map_or_else(|| sym.clone(), |(_, value)| value.to_string());
+                Rule {
+                    lhs: substitute(&self.lhs),
+                    rhs: self.rhs.iter().map(substitute).collect(),
+                    weight: self.weight,
+                }
+            })
+            .collect()
+    }
+}
+
+impl EbnfRule {
+    pub fn new(lhs: &str, ebnf: &str) -> Self {
+        EbnfRule { lhs: lhs.to_string(), ebnf: ebnf.to_string(), weight: default_weight() }
+    }
+
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Compiles the rule into plain rules, adding the auxiliary rules it
+    /// needs to `auxiliary`, see `ebnf::compile`.
+    pub fn compile(&self, auxiliary: &mut Vec<Rule>) -> Result<Vec<Rule>> {
+        let mut rules = Vec::new();
+        for alternative in ebnf::parse_str(&self.ebnf)? {
+            rules.extend(ebnf::compile(&self.lhs, &alternative, self.weight, auxiliary));
         }
+        Ok(rules)
     }
 }
 
 impl fmt::Display for Grammar {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut result = write!(f, "grammar rules:");
-        match result {
-            Err(e) => return Err(e),
-            _ => {}
-        }
+        write!(f, "grammar rules:")?;
         for rule in &self.rules {
-            result = write!(f, "\n  {}", rule);
+            write!(f, "\n  {}", rule)?;
         }
-        //write!(f, "{:?}", self.rules)
-        result
+        Ok(())
     }
 }
 
 impl fmt::Display for Rule {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} --> {}", self.lhs, self.rhs.join(" "))
+        write!(f, "{} --> {}", self.lhs, self.rhs.join(" "))?;
+        if !is_default_weight(&self.weight) {
+            write!(f, " [{}]", self.weight)?;
+        }
+        Ok(())
     }
 }
 
@@ -66,6 +503,42 @@ mod tests {
         assert_eq!(format!("{}", rule), "test --> ");
     }
 
+    #[test]
+    fn rule_accessors_return_lhs_and_rhs() {
+        let rule = Rule::new("S", vec!(String::from("NP"), String::from("VP")));
+
+        assert_eq!(rule.lhs(), "S");
+        assert_eq!(rule.rhs(), &["NP", "VP"]);
+    }
+
+    #[test]
+    fn new_creates_rule_with_weight_one() {
+        let rule = Rule::new("S", Vec::new());
+
+        assert_eq!(rule.weight(), 1.0);
+    }
+
+    #[test]
+    fn format_rule_with_weight() {
+        let rule = Rule::new("S", vec!(String::from("NP"), String::from("VP"))).with_weight(0.25);
+
+        assert_eq!(rule.weight(), 0.25);
+        assert_eq!(format!("{}", rule), "S --> NP VP [0.25]");
+    }
+
+    #[test]
+    fn normalize_weights_sums_to_one_per_category() {
+        let mut grammar = Grammar::from_rules(vec!(
+            Rule::new("NP", Vec::new()).with_weight(3.0),
+            Rule::new("NP", Vec::new()),
+            Rule::new("VP", Vec::new()).with_weight(2.0),
+        ));
+        grammar.normalize_weights();
+
+        let weights: Vec<f64> = grammar.rules.iter().map(Rule::weight).collect();
+        assert_eq!(weights, vec!(0.75, 0.25, 1.0));
+    }
+
     #[test]
     fn new_creates_empty_grammar() {
         let grammar = Grammar::new();
@@ -85,4 +558,152 @@ mod tests {
             "grammar rules:\n  S --> "
         );
     }
+
+    #[test]
+    fn validate_finds_the_first_bad_rule() {
+        let rhs = vec!("a".to_string());
+        let mut grammar = Grammar::from_rules(vec!(
+            Rule::new("S", rhs.clone()),
+            Rule::new("S", rhs.clone()).with_weight(-1.0),
+            Rule::new("S", Vec::new()),
+        ));
+
+        assert_eq!(grammar.validate(), Err(GrammarError::InvalidWeight { rule: 1, weight: -1.0 }));
+        grammar.rules.remove(1);
+        assert_eq!(grammar.validate(), Ok(()));
+        grammar.rules.push(Rule::new("@Adj+", rhs.clone()));
+        assert_eq!(grammar.validate(), Ok(()));
+        grammar.rules.push(Rule::new("@Foo", rhs.clone()));
+        assert_eq!(grammar.validate(), Err(GrammarError::ReservedPrefix { rule: 3, lhs: "@Foo".to_string() }));
+        grammar.rules.pop();
+        grammar.rules.push(Rule::new("", rhs));
+        assert_eq!(grammar.validate(), Err(GrammarError::EmptyLhs { rule: 3 }));
+    }
+
+    #[test]
+    fn schema_expands_to_a_rule_per_value() {
+        let rhs = vec!("X".to_string(), "Conj".to_string(), "X".to_string());
+        let vars: BTreeMap<String, Vec<String>> = vec!(
+            ("X".to_string(), vec!("NP".to_string(), "VP".to_string(), "S".to_string())),
+        ).into_iter().collect();
+        let grammar = Grammar::from_entries(vec!(
+            Entry::Rule(Rule::new("Conj", vec!("and".to_string()))),
+            Entry::Schema(Schema::new("X", rhs, vars)),
+        )).unwrap();
+
+        assert_eq!(
+            grammar.to_string(),
+            "grammar rules:\n  Conj --> and\n  NP --> NP Conj NP\n  VP --> VP Conj VP\n  S --> S Conj S",
+        );
+    }
+
+    #[test]
+    fn schema_with_two_variables_takes_all_combinations() {
+        let schema: Schema = ron::de::from_str(
+            r#"(lhs: "X", rhs: ["X", "Y"], weight: 0.5, vars: {"X": ["A", "B"], "Y": ["c", "d"]})"#
+        ).unwrap();
+
+        let rules: Vec<String> = schema.expand().iter().map(Rule::to_string).collect();
+        assert_eq!(rules, vec!("A --> A c [0.5]", "A --> A d [0.5]", "B --> B c [0.5]", "B --> B d [0.5]"));
+    }
+
+    #[test]
+    fn entries_are_rules_or_schemas() {
+        let entries: Vec<Entry> = ron::de::from_str(
+            r#"[(lhs: "S", rhs: ["NP", "VP"]), (lhs: "X", rhs: ["X", "and", "X"], vars: {"X": ["NP"]})]"#
+        ).unwrap();
+
+        assert!(matches!(entries[0], Entry::Rule(_)));
+        assert!(matches!(entries[1], Entry::Schema(_)));
+        assert_eq!(Grammar::from_entries(entries).unwrap().rules[1].to_string(), "NP --> NP and NP");
+    }
+
+    #[test]
+    fn ebnf_entries_are_compiled_with_their_auxiliary_rules_last() {
+        let entries: Vec<Entry> = ron::de::from_str(
+            r#"[(lhs: "NP", ebnf: "Det Adj+ \"(\" Noun", weight: 0.5), (lhs: "Adj", rhs: ["old"])]"#
+        ).unwrap();
+
+        assert!(matches!(entries[0], Entry::Ebnf(_)));
+        let rules: Vec<String> = Grammar::from_entries(entries).unwrap().rules.iter().map(Rule::to_string).collect();
+        assert_eq!(rules, vec!(
+            "NP --> Det @Adj+ ( Noun [0.5]",
+            "Adj --> old",
+            "@Adj+ --> Adj [0.5]",
+            "@Adj+ --> @Adj+ Adj [0.5]",
+        ));
+        assert!(EbnfRule::new("NP", "Det (Noun").compile(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn start_defaults_to_the_first_category() {
+        let grammar = Grammar::from_rules(vec!(Rule::new("S", vec!("NP".to_string()))));
+
+        assert_eq!(Grammar::new().start(), None);
+        assert_eq!(grammar.start(), Some("S"));
+        assert_eq!(grammar.clone().with_start("NP").start(), Some("NP"));
+        assert_eq!(
+            grammar.with_start("NP").validate(),
+            Err(GrammarError::UnknownStart { start: "NP".to_string() }),
+        );
+    }
+
+    #[test]
+    fn files_have_entries_with_optional_metadata() {
+        let (grammar, entries) = ron::de::from_str::<GrammarFile>(
+            r#"(name: "Toy", version: "1.0", start: "VP", rules: [(lhs: "VP", rhs: ["sleeps"])])"#
+        ).unwrap().into_parts();
+
+        assert_eq!(grammar.name.as_deref(), Some("Toy"));
+        assert_eq!(grammar.start(), Some("VP"));
+        assert_eq!(grammar.description, None);
+        assert_eq!(entries.len(), 1);
+        let (with_some, _) = ron::de::from_str::<GrammarFile>(r#"(name: Some("Toy"), start: None, rules: [])"#)
+            .unwrap()
+            .into_parts();
+        assert_eq!((with_some.name.as_deref(), with_some.start), (Some("Toy"), None));
+        let (grammar, entries) = ron::de::from_str::<GrammarFile>(r#"[(lhs: "VP", rhs: ["sleeps"])]"#)
+            .unwrap()
+            .into_parts();
+        assert_eq!(grammar, Grammar::new());
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn entries_with_unknown_or_missing_fields_are_errors() {
+        let message = |text: &str| ron::de::from_str::<GrammarFile>(text).unwrap_err().to_string();
+
+        assert_eq!(
+            message(r#"[(lhs: "S", rhs: ["NP"]), (lhs: "S", rsh: ["VP"])]"#),
+            "unknown field `rsh`, expected one of `lhs`, `rhs`, `ebnf`, `weight`, `vars`, `include`, `namespace`",
+        );
+        assert_eq!(message(r#"[(lhs: "S")]"#), "missing field `rhs` or `ebnf`");
+        assert_eq!(message(r#"[(include: "a.ron", lhs: "S")]"#), "an include has only the fields `include` and `namespace`");
+        assert_eq!(message(r#"(nmae: "Toy", rules: [])"#), "unknown field `nmae`, expected one of `name`, `version`, `description`, `start`, `rules`");
+        assert!(message(r#"(name: "Toy", rules: [(lhs: "S", ebnf: "NP", rhs: [])])"#).contains("either `rhs` or `ebnf`"));
+    }
+
+    #[test]
+    fn includes_are_entries() {
+        let entries: Vec<Entry> = ron::de::from_str(
+            r#"[(include: "lexicon.ron"), (include: "np.ron", namespace: "np")]"#
+        ).unwrap();
+
+        assert_eq!(entries[1], Entry::Include(Include {
+            include: "np.ron".to_string(),
+            namespace: Some("np".to_string()),
+        }));
+        assert!(Grammar::from_entries(entries).unwrap().rules.is_empty());
+    }
+
+    #[test]
+    fn namespace_prefixes_categories_only() {
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("NP", vec!("Det".to_string(), "Noun".to_string())),
+            Rule::new("Det", vec!("the".to_string())),
+        ));
+
+        let rules: Vec<String> = grammar.with_namespace("np").rules.iter().map(Rule::to_string).collect();
+        assert_eq!(rules, vec!("np::NP --> np::Det Noun", "np::Det --> the"));
+    }
 } // mod tests
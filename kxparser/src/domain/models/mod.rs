@@ -1,3 +1,7 @@
 pub mod grammar;
 pub mod edge;
 pub mod chart;
+pub mod tree;
+pub mod compiled_grammar;
+pub mod features;
+pub mod ebnf;
@@ -0,0 +1,118 @@
+use std::fmt;
+
+use crate::domain::models::ebnf;
+
+/// A parse tree. Its leaves are words, while a category with an empty
+/// right-hand side is a tree without children.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tree {
+    root: String,
+    children: Vec<Tree>,
+    leaf: bool,
+}
+
+impl Tree {
+    pub fn new(root: &str, children: Vec<Tree>) -> Self {
+        Tree {
+            root: root.to_string(),
+            children,
+            leaf: false,
+        }
+    }
+
+    pub fn leaf(root: &str) -> Self {
+        Tree {
+            root: root.to_string(),
+            children: Vec::new(),
+            leaf: true,
+        }
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn children(&self) -> &[Tree] {
+        &self.children
+    }
+
+    /// Whether the tree is a word.
+    pub fn is_leaf(&self) -> bool {
+        self.leaf
+    }
+
+    /// Replaces the auxiliary categories made for EBNF operators by their
+    /// children, giving the structure of the rules as written.
+    pub fn flatten(&self) -> Tree {
+        let mut children = Vec::new();
+        for child in &self.children {
+            let child = child.flatten();
+            if ebnf::is_auxiliary(&child.root) && !child.is_leaf() {
+                children.extend(child.children);
+            } else {
+                children.push(child);
+            }
+        }
+        Tree { root: self.root.clone(), children, leaf: self.leaf }
+    }
+}
+
+/// Formats the tree with brackets, `(S (NP ...) (VP ...))`, where an
+/// empty category is `(Det)`.
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_leaf() {
+            return write!(f, "{}", self.root);
+        }
+        write!(f, "({}", self.root)?;
+        for child in &self.children {
+            write!(f, " {}", child)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaf_has_no_children() {
+        let tree = Tree::leaf("lion");
+
+        assert_eq!(tree.root(), "lion");
+        assert!(tree.is_leaf());
+    }
+
+    #[test]
+    fn new_creates_tree() {
+        let tree = Tree::new("Noun", vec!(Tree::leaf("lion")));
+
+        assert_eq!(tree.root(), "Noun");
+        assert_eq!(tree.children(), &[Tree::leaf("lion")]);
+        assert!(!tree.is_leaf());
+    }
+
+    #[test]
+    fn format_tree() {
+        let tree = Tree::new("NP", vec!(
+            Tree::new("Det", vec!(Tree::leaf("the"))),
+            Tree::new("Noun", vec!(Tree::leaf("lion"))),
+        ));
+
+        assert_eq!(format!("{}", tree), "(NP (Det the) (Noun lion))");
+        assert_eq!(format!("{}", Tree::leaf("lion")), "lion");
+        assert_eq!(format!("{}", Tree::new("Det", Vec::new())), "(Det)");
+    }
+
+    #[test]
+    fn flatten_removes_auxiliary_categories() {
+        let adjs = Tree::new("@Adj+", vec!(
+            Tree::new("@Adj+", vec!(Tree::new("Adj", vec!(Tree::leaf("big"))))),
+            Tree::new("Adj", vec!(Tree::leaf("old"))),
+        ));
+        let tree = Tree::new("NP", vec!(adjs, Tree::new("Noun", vec!(Tree::leaf("lion")))));
+
+        assert_eq!(tree.flatten().to_string(), "(NP (Adj big) (Adj old) (Noun lion))");
+    }
+}
@@ -0,0 +1,201 @@
+use std::{fmt, io, path::{Path, PathBuf}};
+
+use crate::parser::{Limit, LimitExceeded};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong in this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A grammar file could not be parsed.
+    Format {
+        /// The file with the error, if known, e.g. an included file.
+        file: Option<PathBuf>,
+        /// The line and column of the error, counting from 1, if known.
+        position: Option<(usize, usize)>,
+        message: String,
+    },
+    /// A grammar is not usable by the parsers.
+    Grammar(GrammarError),
+    /// A parse was stopped by one of its `Limits`.
+    Limit(Limit),
+    /// The cost of an edit for error correction is negative or not finite.
+    InvalidCost { edit: &'static str, cost: f64 },
+}
+
+/// Why a grammar is not usable. Rules are numbered from 0.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GrammarError {
+    EmptyLhs { rule: usize },
+    /// Rules with an empty right-hand side are not supported by the
+    /// `Stolcke` parser.
+    EmptyRhs { rule: usize, lhs: String },
+    /// There are no rules for the start category.
+    UnknownStart { start: String },
+    /// A category in the namespace of an included file has no rules.
+    UndefinedCategory { category: String },
+    /// Only the auxiliary categories of EBNF operators may start with the
+    /// prefix `@`.
+    ReservedPrefix { rule: usize, lhs: String },
+    /// Weights must be finite and not negative.
+    InvalidWeight { rule: usize, weight: f64 },
+    /// The weights of the rules for a category do not sum to 1, as a
+    /// probabilistic parser needs.
+    WeightSum { lhs: String, total: f64 },
+    /// The weights are not probabilities that a probabilistic parser can
+    /// sum over, e.g. a left-recursive category that never ends.
+    Unnormalizable,
+}
+
+impl Error {
+    /// Sets the file of a format error that does not have one yet.
+    pub fn in_file(self, path: &Path) -> Self {
+        match self {
+            Error::Format { file: None, position, message } => {
+                Error::Format { file: Some(path.to_path_buf()), position, message }
+            }
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Format { file, position, message } => {
+                if let Some(file) = file {
+                    write!(f, "{}:", file.display())?;
+                    if position.is_none() {
+                        write!(f, " ")?;
+                    }
+                }
+                if let Some((line, column)) = position {
+                    write!(f, "{}:{}: ", line, column)?;
+                }
+                write!(f, "{}", message)
+            }
+            Error::Grammar(e) => write!(f, "invalid grammar: {}", e),
+            Error::Limit(limit) => write!(f, "parse stopped: {}", limit),
+            Error::InvalidCost { edit, cost } => write!(f, "invalid {} cost {}", edit, cost),
+        }
+    }
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrammarError::EmptyLhs { rule } => write!(f, "rule {} has an empty left-hand side", rule),
+            GrammarError::EmptyRhs { rule, lhs } => {
+                write!(f, "rule {} for {} has an empty right-hand side", rule, lhs)
+            }
+            GrammarError::UnknownStart { start } => write!(f, "no rules for the start category {}", start),
+            GrammarError::UndefinedCategory { category } => {
+                write!(f, "no rules for {} in its namespace", category)
+            }
+            GrammarError::ReservedPrefix { rule, lhs } => {
+                write!(f, "rule {} for {} uses the prefix '@' of auxiliary categories", rule, lhs)
+            }
+            GrammarError::InvalidWeight { rule, weight } => {
+                write!(f, "rule {} has an invalid weight {}", rule, weight)
+            }
+            GrammarError::WeightSum { lhs, total } => {
+                write!(f, "the weights of the rules for {} sum to {}, not 1", lhs, total)
+            }
+            GrammarError::Unnormalizable => write!(f, "the rule weights cannot be read as probabilities"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<GrammarError> for Error {
+    fn from(e: GrammarError) -> Self {
+        Error::Grammar(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Self {
+        match e.code {
+            ron::error::ErrorCode::Io(message) => Error::Io(io::Error::other(message)),
+            code => {
+                let position = Some((e.position.line, e.position.col)).filter(|&(line, _)| line > 0);
+                Error::Format { file: None, position, message: code.to_string() }
+            }
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            return Error::Io(io::Error::other(e.to_string()));
+        }
+        let position = Some((e.line(), e.column())).filter(|&(line, _)| line > 0);
+        // the message of `e` ends with the position, which is kept separately
+        let message = e.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(i) if position.is_some() => message[..i].to_string(),
+            _ => message,
+        };
+        Error::Format { file: None, position, message }
+    }
+}
+
+impl From<LimitExceeded<'_>> for Error {
+    fn from(e: LimitExceeded) -> Self {
+        Error::Limit(e.limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ron_errors_keep_their_position() {
+        let e: Error = ron::de::from_str::<Vec<u32>>("[1,\n  x]").unwrap_err().into();
+
+        match e {
+            Error::Format { position: Some((2, _)), .. } => (),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn format_errors_display_their_file() {
+        let e = Error::Format { file: None, position: Some((3, 7)), message: "expected '->'".to_string() };
+        let file = Path::new("grammars/lexicon.txt");
+
+        assert_eq!(e.to_string(), "3:7: expected '->'");
+        let e = e.in_file(file).in_file(Path::new("main.ron"));
+        assert_eq!(e.to_string(), "grammars/lexicon.txt:3:7: expected '->'");
+        let e = Error::Format { file: None, position: None, message: "include cycle".to_string() };
+        assert_eq!(e.in_file(file).to_string(), "grammars/lexicon.txt: include cycle");
+    }
+
+    #[test]
+    fn grammar_errors_display_the_rule() {
+        let e = Error::from(GrammarError::EmptyRhs { rule: 3, lhs: "NP".to_string() });
+
+        assert_eq!(e.to_string(), "invalid grammar: rule 3 for NP has an empty right-hand side");
+    }
+}
@@ -0,0 +1,289 @@
+//! Random sentences from a grammar.
+//!
+//! A `Generator` expands categories top-down, choosing each rule with a
+//! probability proportional to its weight among the rules for its
+//! category. The trees are at most `max_depth` deep: when a rule could not
+//! be finished within the depth that is left, it is not chosen, so deep
+//! recursion is cut short at the price of skewing the distribution near
+//! the limit.
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::domain::models::{
+    grammar::{Grammar, Rule},
+    tree::Tree,
+};
+
+/// A small seedable random number generator, SplitMix64.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// A generator seeded from the clock.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos());
+        Rng::seeded(nanos as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// A generated sentence with its tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sentence<'g> {
+    words: Vec<&'g str>,
+    tree: Tree,
+}
+
+impl<'g> Sentence<'g> {
+    pub fn words(&self) -> &[&'g str] {
+        &self.words
+    }
+
+    /// The tree, without the auxiliary categories of EBNF operators.
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+}
+
+/// Generates random sentences of a grammar.
+///
+/// Symbols without rules are words. The default depth limit is
+/// `DEFAULT_MAX_DEPTH`, and the random number generator is seeded from
+/// the clock unless `with_seed` is used.
+pub struct Generator<'g> {
+    start: Option<&'g str>,
+    rules: HashMap<&'g str, Vec<&'g Rule>>,
+    /// The depth of the shallowest tree for each category, missing for
+    /// categories that cannot be finished.
+    min_depths: HashMap<&'g str, usize>,
+    max_depth: usize,
+    rng: Rng,
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 20;
+
+impl<'g> Generator<'g> {
+    pub fn from_grammar(grammar: &'g Grammar) -> Self {
+        let mut rules: HashMap<&str, Vec<&Rule>> = HashMap::new();
+        for rule in &grammar.rules {
+            rules.entry(rule.lhs()).or_default().push(rule);
+        }
+        let min_depths = min_depths(&rules);
+        Generator {
+            start: grammar.start(),
+            rules,
+            min_depths,
+            max_depth: DEFAULT_MAX_DEPTH,
+            rng: Rng::from_time(),
+        }
+    }
+
+    /// The deepest trees generated, where a word has depth 0 and a
+    /// category with only words below it has depth 1.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Seeds the random number generator, so that the same sentences are
+    /// generated each time.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::seeded(seed);
+        self
+    }
+
+    /// A random sentence of the start category, or `None` if the grammar
+    /// has no rules or no tree within the depth limit.
+    pub fn generate(&mut self) -> Option<Sentence<'g>> {
+        self.generate_from(self.start?)
+    }
+
+    /// A random sentence of `cat`, or `None` if `cat` has no rules or no
+    /// tree within the depth limit.
+    pub fn generate_from(&mut self, cat: &str) -> Option<Sentence<'g>> {
+        let cat = self.rules.get_key_value(cat)?.0;
+        let mut words = Vec::new();
+        let tree = self.expand(cat, self.max_depth, &mut words)?;
+        Some(Sentence { words, tree: tree.flatten() })
+    }
+
+    /// The depth of a tree for `rule` with shallowest subtrees, `None` if
+    /// the rule is never chosen.
+    fn min_depth(&self, rule: &Rule) -> Option<usize> {
+        min_rule_depth(rule, &self.rules, &self.min_depths)
+    }
+
+    fn expand(&mut self, sym: &'g str, depth: usize, words: &mut Vec<&'g str>) -> Option<Tree> {
+        let rules = match self.rules.get(sym) {
+            None => {
+                words.push(sym);
+                return Some(Tree::leaf(sym));
+            }
+            Some(rules) => rules,
+        };
+        let choices: Vec<&'g Rule> = rules.iter()
+            .copied()
+            .filter(|&rule| self.min_depth(rule).is_some_and(|min| min <= depth))
+            .collect();
+        let total: f64 = choices.iter().map(|rule| rule.weight()).sum();
+        let mut point = self.rng.next_f64() * total;
+        let rule = *choices.iter()
+            .find(|rule| {
+                point -= rule.weight();
+                point < 0.0
+            })
+            .or_else(|| choices.last())?;
+        let children = rule.rhs().iter()
+            .map(|sym| self.expand(sym, depth - 1, words))
+            .collect::<Option<Vec<Tree>>>()?;
+        Some(Tree::new(sym, children))
+    }
+}
+
+/// Rules of weight 0 are never chosen, so they have no trees.
+fn min_rule_depth(rule: &Rule, rules: &HashMap<&str, Vec<&Rule>>, min_depths: &HashMap<&str, usize>) -> Option<usize> {
+    if rule.weight() <= 0.0 {
+        return None;
+    }
+    let mut depth = 0;
+    for sym in rule.rhs() {
+        let sym = sym.as_str();
+        if rules.contains_key(sym) {
+            depth = depth.max(*min_depths.get(sym)?);
+        }
+    }
+    Some(depth + 1)
+}
+
+/// The depth of the shallowest tree of each category that has one.
+fn min_depths<'g>(rules: &HashMap<&'g str, Vec<&'g Rule>>) -> HashMap<&'g str, usize> {
+    let mut min_depths = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (&cat, cat_rules) in rules {
+            let depth = cat_rules.iter()
+                .filter_map(|rule| min_rule_depth(rule, rules, &min_depths))
+                .min();
+            if let Some(depth) = depth {
+                if min_depths.get(cat).is_none_or(|&old| depth < old) {
+                    min_depths.insert(cat, depth);
+                    changed = true;
+                }
+            }
+        }
+    }
+    min_depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{example_grammar, Earley3, Parser};
+
+    fn depth(tree: &Tree) -> usize {
+        tree.children().iter().map(|child| depth(child) + 1).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn same_seed_gives_same_sentences() {
+        let grammar = example_grammar();
+        let sentences = |seed| {
+            let mut generator = Generator::from_grammar(&grammar).with_seed(seed);
+            (0..10).map(|_| generator.generate().unwrap()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(sentences(7), sentences(7));
+        assert_ne!(sentences(7), sentences(8));
+    }
+
+    #[test]
+    fn sentences_are_licensed_by_the_grammar() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let mut generator = Generator::from_grammar(&grammar).with_seed(1).with_max_depth(8);
+
+        for _ in 0..50 {
+            let sentence = generator.generate().unwrap();
+            assert!(parser.recognize(sentence.words()), "{}", sentence.words().join(" "));
+            assert!(depth(sentence.tree()) <= 8, "{}", sentence.tree());
+            assert_eq!(sentence.tree().root(), "S");
+        }
+    }
+
+    #[test]
+    fn rules_are_chosen_by_weight() {
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("S", vec!("a".to_string())).with_weight(0.9),
+            Rule::new("S", vec!("b".to_string())).with_weight(0.1),
+        ));
+        let mut generator = Generator::from_grammar(&grammar).with_seed(3);
+
+        let a = (0..1000).filter(|_| generator.generate().unwrap().words() == ["a"]).count();
+        assert!((850..950).contains(&a), "{}", a);
+    }
+
+    #[test]
+    fn categories_with_only_rules_of_weight_zero_are_not_chosen() {
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("S", vec!("A".to_string())),
+            Rule::new("S", vec!("b".to_string())),
+            Rule::new("A", vec!("a".to_string())).with_weight(0.0),
+        ));
+        let mut generator = Generator::from_grammar(&grammar).with_seed(5);
+
+        for _ in 0..100 {
+            assert_eq!(generator.generate().unwrap().words(), ["b"]);
+        }
+    }
+
+    #[test]
+    fn depth_limit_cuts_recursion_short() {
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("S", vec!("S".to_string(), "S".to_string())).with_weight(0.9),
+            Rule::new("S", vec!("x".to_string())).with_weight(0.1),
+            Rule::new("Loop", vec!("Loop".to_string())),
+        ));
+        let mut generator = Generator::from_grammar(&grammar).with_seed(5).with_max_depth(4);
+
+        for _ in 0..20 {
+            let sentence = generator.generate().unwrap();
+            assert!(depth(sentence.tree()) <= 4);
+            assert!(sentence.words().len() <= 8);
+        }
+        assert_eq!(generator.generate_from("Loop"), None);
+        assert_eq!(generator.generate_from("x"), None);
+        assert_eq!(generator.with_max_depth(0).generate(), None);
+    }
+
+    #[test]
+    fn ebnf_categories_are_flattened_and_empty_rules_allowed() {
+        let grammar = crate::io::text::parse_grammar("NP -> Adj* Noun\nAdj -> \"big\"\nNoun -> \"lion\" |\n").unwrap();
+        let mut generator = Generator::from_grammar(&grammar).with_seed(11);
+
+        for _ in 0..20 {
+            let sentence = generator.generate().unwrap();
+            assert!(sentence.tree().children().iter().all(|child| matches!(child.root(), "Adj" | "Noun")));
+            assert!(sentence.words().iter().all(|word| matches!(*word, "big" | "lion")));
+        }
+    }
+}
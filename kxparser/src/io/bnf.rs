@@ -0,0 +1,254 @@
+//! Grammars in BNF, with the common EBNF extensions.
+//!
+//! ```text
+//! ; comments run to the end of the line, /* or are delimited */
+//! <sentence> ::= <noun phrase> <verb phrase>
+//! <noun phrase> ::= "the" <adjective>* <noun>
+//!                 | <name>
+//! VP ::= V NP? ( PP | Adv )*
+//! ```
+//!
+//! Nonterminals are written in angle brackets or as bare words, and
+//! terminals in single or double quotes. A rule continues until the next
+//! `::=`. Groups `( )`, optional parts `[ ]`, repetitions `{ }` and the
+//! postfix operators `?`, `*` and `+` are compiled as described in
+//! `ebnf`. ABNF and W3C notations for characters, like `%x41`, `#x41` or
+//! character classes, are not supported.
+
+use crate::domain::models::{
+    ebnf,
+    grammar::{Grammar, Rule},
+};
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ebnf(ebnf::Token),
+    Define,
+    /// The end of `[ ]` or `{ }`, a closing parenthesis with an operator.
+    CloseWith(char),
+}
+
+fn error(line: usize, column: usize, message: &str) -> Error {
+    Error::Format { file: None, position: Some((line, column)), message: message.to_string() }
+}
+
+/// The closing bracket for an opening one.
+fn closing(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut in_comment = None;
+    // the brackets open in the current rule
+    let mut open = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let column = i + 1;
+            let rest: String = chars[i..].iter().take(3).collect();
+            if in_comment.is_some() {
+                if rest.starts_with("*/") {
+                    in_comment = None;
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+            let c = chars[i];
+            let token = match c {
+                _ if c.is_whitespace() => {
+                    i += 1;
+                    continue;
+                }
+                ';' => break,
+                '/' if rest.starts_with("/*") => {
+                    in_comment = Some((number, column));
+                    i += 2;
+                    continue;
+                }
+                ':' if rest == "::=" => {
+                    open.clear();
+                    i += 3;
+                    Token::Define
+                }
+                '|' => Token::Ebnf(ebnf::Token::Bar),
+                ')' | ']' | '}' if open.last().is_some_and(|&bracket| closing(bracket) != c) => {
+                    let expected = closing(*open.last().unwrap());
+                    return Err(error(number, column, &format!("expected '{}'", expected)));
+                }
+                '(' | '[' | '{' => {
+                    open.push(c);
+                    Token::Ebnf(ebnf::Token::Open)
+                }
+                ')' | ']' | '}' => {
+                    open.pop();
+                    match c {
+                        ')' => Token::Ebnf(ebnf::Token::Close),
+                        ']' => Token::CloseWith('?'),
+                        _ => Token::CloseWith('*'),
+                    }
+                }
+                '?' | '*' | '+' => Token::Ebnf(ebnf::Token::Operator(c)),
+                '\'' | '"' => {
+                    let end = chars[i + 1..].iter().position(|&other| other == c)
+                        .ok_or_else(|| error(number, column, "unterminated string"))?;
+                    let terminal: String = chars[i + 1..i + 1 + end].iter().collect();
+                    i += end + 2;
+                    tokens.push((number, column, Token::Ebnf(ebnf::Token::Symbol(terminal))));
+                    continue;
+                }
+                '<' => {
+                    let end = chars[i + 1..].iter().position(|&c| c == '>')
+                        .ok_or_else(|| error(number, column, "missing '>'"))?;
+                    i += end + 2;
+                    tokens.push((number, column, Token::Ebnf(ebnf::Token::Symbol(chars[column..column + end].iter().collect()))));
+                    continue;
+                }
+                _ if c.is_alphanumeric() || c == '_' => {
+                    let start = i;
+                    while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '-' | '.')) {
+                        i += 1;
+                    }
+                    tokens.push((number, column, Token::Ebnf(ebnf::Token::Symbol(chars[start..i].iter().collect()))));
+                    continue;
+                }
+                _ => {
+                    let construct: String = chars[i..].iter().take_while(|c| !c.is_whitespace()).collect();
+                    return Err(error(number, column, &format!("unsupported construct '{}'", construct)));
+                }
+            };
+            if !matches!(token, Token::Define) {
+                i += 1;
+            }
+            tokens.push((number, column, token));
+        }
+    }
+    if let Some((line, column)) = in_comment {
+        return Err(error(line, column, "unterminated comment"));
+    }
+    Ok(tokens)
+}
+
+/// Reads a grammar in BNF, compiling its EBNF operators. The auxiliary
+/// rules come after all others.
+pub fn parse_grammar(text: &str) -> Result<Grammar> {
+    let tokens = tokenize(text)?;
+    let end = (text.lines().count().max(1), text.lines().last().map_or(0, |line| line.chars().count()) + 1);
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut auxiliary = Vec::new();
+    let mut rhs = Vec::new();
+    let mut lhs: Option<String> = None;
+    let mut compile = |lhs: &str, rhs: &[((usize, usize), ebnf::Token)], end: (usize, usize)| -> Result<()> {
+        for alternative in ebnf::parse(rhs, end, |(line, column), message| error(line, column, message))? {
+            rules.extend(ebnf::compile(lhs, &alternative, 1.0, &mut auxiliary));
+        }
+        Ok(())
+    };
+    let mut i = 0;
+    while i < tokens.len() {
+        let (line, column, token) = &tokens[i];
+        let position = (*line, *column);
+        match token {
+            Token::Ebnf(ebnf::Token::Symbol(symbol)) if matches!(tokens.get(i + 1), Some((_, _, Token::Define))) => {
+                if let Some(lhs) = lhs.take() {
+                    compile(&lhs, &std::mem::take(&mut rhs), position)?;
+                }
+                lhs = Some(symbol.clone());
+                i += 2;
+                continue;
+            }
+            _ if lhs.is_none() => return Err(error(*line, *column, "expected a rule")),
+            Token::Ebnf(token) => rhs.push((position, token.clone())),
+            Token::CloseWith(op) => {
+                rhs.push((position, ebnf::Token::Close));
+                rhs.push((position, ebnf::Token::Operator(*op)));
+            }
+            Token::Define => return Err(error(*line, *column, "unexpected '::='")),
+        }
+        i += 1;
+    }
+    if let Some(lhs) = lhs {
+        compile(&lhs, &rhs, end)?;
+    }
+    rules.extend(auxiliary);
+    Ok(Grammar::from_rules(rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(text: &str) -> Vec<String> {
+        parse_grammar(text).unwrap().rules.iter().map(Rule::to_string).collect()
+    }
+
+    #[test]
+    fn reads_classic_bnf() {
+        assert_eq!(
+            rules(
+                "<sentence> ::= <noun phrase> <verb>\n\
+                 <noun phrase> ::= \"the\" <noun> ; a comment\n\
+                 \x20              | <name>\n\
+                 <noun> ::= 'lion' | 'zebra'\n"
+            ),
+            vec!(
+                "sentence --> noun phrase verb",
+                "noun phrase --> the noun",
+                "noun phrase --> name",
+                "noun --> lion",
+                "noun --> zebra",
+            ),
+        );
+    }
+
+    #[test]
+    fn reads_ebnf_extensions() {
+        assert_eq!(
+            rules(
+                "/* groups, options and repetitions */\n\
+                 NP ::= Det? N { PP }\n\
+                 VP ::= V [ NP ] ( 'quickly' | Adv )+\n"
+            ),
+            vec!(
+                "NP --> N [0.25]",
+                "NP --> N @PP+ [0.25]",
+                "NP --> Det N [0.25]",
+                "NP --> Det N @PP+ [0.25]",
+                "VP --> V @(quickly | Adv)+ [0.5]",
+                "VP --> V NP @(quickly | Adv)+ [0.5]",
+                "@PP+ --> PP [0.5]",
+                "@PP+ --> @PP+ PP [0.5]",
+                "@(quickly | Adv)+ --> quickly [0.25]",
+                "@(quickly | Adv)+ --> @(quickly | Adv)+ quickly [0.25]",
+                "@(quickly | Adv)+ --> Adv [0.25]",
+                "@(quickly | Adv)+ --> @(quickly | Adv)+ Adv [0.25]",
+            ),
+        );
+    }
+
+    #[test]
+    fn unsupported_constructs_are_errors() {
+        let message = |text: &str| parse_grammar(text).unwrap_err().to_string();
+
+        assert_eq!(message("digit ::= %x30-39\n"), "1:11: unsupported construct '%x30-39'");
+        assert_eq!(message("S ::= A\n  / B\n"), "2:3: unsupported construct '/'");
+        assert_eq!(message("S = A\n"), "1:3: unsupported construct '='");
+        assert_eq!(message("S ::= ( A\n"), "1:10: missing ')'");
+        assert_eq!(message("S ::= ( A ]\n"), "1:11: expected ')'");
+        assert_eq!(message("S ::= [ A }\n"), "1:11: expected ']'");
+        assert_eq!(message("S ::= { [ A ) }\n"), "1:13: expected ']'");
+        assert_eq!(message("S ::= ( A\nT ::= B ]\n"), "2:1: missing ')'");
+        assert_eq!(message("S ::= * A\n"), "1:7: '*' without an operand");
+        assert_eq!(message("A B\n"), "1:1: expected a rule");
+        assert_eq!(message("S ::= A /* open\n"), "1:9: unterminated comment");
+    }
+}
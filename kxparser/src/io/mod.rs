@@ -0,0 +1,469 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use crate::domain::models::{
+    ebnf,
+    grammar::{Entry, Grammar, GrammarFile, Include, Rule, NAMESPACE_SEPARATOR},
+};
+use crate::error::{Error, GrammarError, Result};
+
+pub mod bnf;
+pub mod nltk;
+pub mod text;
+
+/// Reads a grammar stored as a RON list of rules, rule schemas and
+/// includes, possibly with metadata as in `GrammarFile`, compiles its
+/// EBNF operators and checks that it is valid.
+///
+/// An included file can be in any format that `read_grammar` reads, and
+/// its rules come after those of the including file, so that the first
+/// rule stays the first. The namespace of an include applies to the
+/// categories of that file only, not to those of the files it includes in
+/// turn, and a symbol in the namespace of an include must have rules. A
+/// file included twice with the same namespace is read once, and a file
+/// including itself, directly or not, is an error.
+pub fn read_grammar_from_ron_file(file_name: &str) -> Result<Grammar> {
+    read_grammar_file(file_name, Format::Ron)
+}
+
+/// Reads a grammar stored in JSON, with the same fields and includes as
+/// `read_grammar_from_ron_file`.
+pub fn read_grammar_from_json_file(file_name: &str) -> Result<Grammar> {
+    read_grammar_file(file_name, Format::Json)
+}
+
+/// The files being read, outermost first, the includes read already with
+/// their namespaces, and all namespaces.
+#[derive(Default)]
+struct Includes {
+    stack: Vec<PathBuf>,
+    done: HashSet<(PathBuf, Option<String>)>,
+    namespaces: HashSet<String>,
+}
+
+/// Reads a RON or JSON grammar file with its includes and checks it.
+fn read_grammar_file(file_name: &str, format: Format) -> Result<Grammar> {
+    let mut includes = Includes::default();
+    let grammar = read_with_includes(Path::new(file_name), format, None, &mut includes)?;
+    let categories: HashSet<&str> = grammar.rules.iter().map(Rule::lhs).collect();
+    for symbol in grammar.rules.iter().flat_map(Rule::rhs) {
+        if let Some((namespace, _)) = symbol.split_once(NAMESPACE_SEPARATOR) {
+            if includes.namespaces.contains(namespace) && !categories.contains(symbol.as_str()) {
+                return Err(GrammarError::UndefinedCategory { category: symbol.clone() }.into());
+            }
+        }
+    }
+    grammar.validate()?;
+    Ok(grammar)
+}
+
+/// Reads a grammar file with its includes and compiles its EBNF
+/// operators, without checking it. The categories of the file itself are
+/// put in `namespace`, and the metadata of included files is left out.
+fn read_with_includes(file_name: &Path, format: Format, namespace: Option<&str>, includes: &mut Includes) -> Result<Grammar> {
+    let path = fs::canonicalize(file_name)?;
+    if let Some(i) = includes.stack.iter().position(|other| *other == path) {
+        let cycle: Vec<String> = includes.stack[i..].iter().chain(Some(&path))
+            .map(|path| path.display().to_string())
+            .collect();
+        return Err(Error::Format { file: None, position: None, message: format!("include cycle {}", cycle.join(" -> ")) });
+    }
+    let in_namespace = |grammar: Grammar| match namespace {
+        Some(namespace) => grammar.with_namespace(namespace),
+        None => grammar,
+    };
+    let source = fs::read_to_string(&path)?;
+    let file = match format {
+        Format::Ron => parse_ron(&source),
+        Format::Json => serde_json::from_str::<GrammarFile>(&source).map_err(Error::from),
+        Format::Text => return text::parse_grammar(&source).map(in_namespace).map_err(|e| e.in_file(&path)),
+        Format::Nltk => return nltk::parse_grammar(&source).map(in_namespace).map_err(|e| e.in_file(&path)),
+        Format::Bnf => return bnf::parse_grammar(&source).map(in_namespace).map_err(|e| e.in_file(&path)),
+    };
+
+    let (metadata, entries) = file.map_err(|e| e.in_file(&path))?.into_parts();
+    let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut included = Vec::new();
+    let mut own = Vec::new();
+    includes.stack.push(path.clone());
+    for entry in entries {
+        match entry {
+            Entry::Include(Include { include, namespace }) => {
+                let file_name = directory.join(&include);
+                let format = Format::from_file_name(&include).map_err(|e| e.in_file(&path))?;
+                includes.namespaces.extend(namespace.clone());
+                let key = (fs::canonicalize(&file_name)?, namespace.clone());
+                if includes.stack.contains(&key.0) || includes.done.insert(key) {
+                    included.push(read_with_includes(&file_name, format, namespace.as_deref(), includes)?);
+                }
+            }
+            entry => own.push(entry),
+        }
+    }
+    includes.stack.pop();
+
+    let rules = in_namespace(Grammar::from_entries(own).map_err(|e| e.in_file(&path))?).rules;
+    let mut grammar = Grammar { rules, ..metadata };
+    for other in included {
+        // the auxiliary categories for the same EBNF operators have the
+        // same rules, which are only kept once
+        let auxiliary: HashSet<String> = grammar.rules.iter()
+            .filter(|rule| ebnf::is_auxiliary(rule.lhs()))
+            .map(|rule| rule.lhs().to_string())
+            .collect();
+        grammar.rules.extend(other.rules.into_iter().filter(|rule| !auxiliary.contains(rule.lhs())));
+    }
+    Ok(grammar)
+}
+
+/// Parses a RON grammar file. The errors of the entries have no position
+/// in RON, so they get the position where reading stopped, in the entry
+/// with the error.
+fn parse_ron(text: &str) -> Result<GrammarFile> {
+    let mut deserializer = ron::de::Deserializer::from_str(text)?;
+    let result = GrammarFile::deserialize(&mut deserializer)
+        .and_then(|file| deserializer.end().map(|()| file));
+    result.map_err(|mut e| {
+        if e.position.line == 0 {
+            let read = &text[..text.len() - deserializer.remainder().len()];
+            e.position.line = read.matches('\n').count() + 1;
+            e.position.col = read.rsplit('\n').next().map_or(0, |line| line.chars().count()) + 1;
+        }
+        e.into()
+    })
+}
+
+/// Reads a grammar in the text format of `text`, compiles its EBNF
+/// operators and checks that it is valid.
+pub fn read_grammar_from_text_file(file_name: &str) -> Result<Grammar> {
+    let grammar = text::parse_grammar(&fs::read_to_string(file_name)?).map_err(|e| e.in_file(Path::new(file_name)))?;
+    grammar.validate()?;
+    Ok(grammar)
+}
+
+/// Reads an NLTK `.cfg` or `.pcfg` grammar and checks that it is valid.
+pub fn read_grammar_from_nltk_file(file_name: &str) -> Result<Grammar> {
+    let grammar = nltk::parse_grammar(&fs::read_to_string(file_name)?).map_err(|e| e.in_file(Path::new(file_name)))?;
+    grammar.validate()?;
+    Ok(grammar)
+}
+
+/// Reads a grammar in BNF, compiles its EBNF operators and checks that
+/// it is valid.
+pub fn read_grammar_from_bnf_file(file_name: &str) -> Result<Grammar> {
+    let grammar = bnf::parse_grammar(&fs::read_to_string(file_name)?).map_err(|e| e.in_file(Path::new(file_name)))?;
+    grammar.validate()?;
+    Ok(grammar)
+}
+
+/// Writes `grammar` in the text format of `text`.
+pub fn write_grammar_to_text_file(grammar: &Grammar, file_name: &str) -> Result<()> {
+    fs::write(file_name, text::write_grammar(grammar))?;
+    Ok(())
+}
+
+/// Writes `grammar` in RON, with its metadata and its list of rules.
+pub fn write_grammar_to_ron_file(grammar: &Grammar, file_name: &str) -> Result<()> {
+    fs::write(file_name, ron::ser::to_string_pretty(grammar, ron::ser::PrettyConfig::new())?)?;
+    Ok(())
+}
+
+/// Writes `grammar` in JSON, with its metadata and its list of rules.
+pub fn write_grammar_to_json_file(grammar: &Grammar, file_name: &str) -> Result<()> {
+    fs::write(file_name, serde_json::to_string_pretty(grammar)?)?;
+    Ok(())
+}
+
+/// The file formats for grammars.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// `.ron`
+    Ron,
+    /// `.json`
+    Json,
+    /// The format of `text`, `.txt`
+    Text,
+    /// NLTK grammars, `.cfg` and `.pcfg`, which can only be read
+    Nltk,
+    /// `.bnf` and `.ebnf`, which can only be read
+    Bnf,
+}
+
+impl Format {
+    /// The format for the extension of `file_name`.
+    pub fn from_file_name(file_name: &str) -> Result<Self> {
+        let extension = Path::new(file_name).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        match extension.to_lowercase().as_str() {
+            "ron" => Ok(Format::Ron),
+            "json" => Ok(Format::Json),
+            "txt" => Ok(Format::Text),
+            "cfg" | "pcfg" => Ok(Format::Nltk),
+            "bnf" | "ebnf" => Ok(Format::Bnf),
+            _ => Err(Error::Format {
+                file: None,
+                position: None,
+                message: format!("unknown grammar format for '{}'", file_name),
+            }),
+        }
+    }
+}
+
+/// Reads a grammar in the format given by the extension of `file_name`.
+pub fn read_grammar(file_name: &str) -> Result<Grammar> {
+    match Format::from_file_name(file_name)? {
+        Format::Ron => read_grammar_from_ron_file(file_name),
+        Format::Json => read_grammar_from_json_file(file_name),
+        Format::Text => read_grammar_from_text_file(file_name),
+        Format::Nltk => read_grammar_from_nltk_file(file_name),
+        Format::Bnf => read_grammar_from_bnf_file(file_name),
+    }
+}
+
+/// Writes `grammar` in the format given by the extension of `file_name`.
+///
+/// The rules compiled from EBNF operators are written as they are, with
+/// their auxiliary categories, so that reading the file gives the same
+/// rules again.
+pub fn write_grammar(grammar: &Grammar, file_name: &str) -> Result<()> {
+    match Format::from_file_name(file_name)? {
+        Format::Ron => write_grammar_to_ron_file(grammar, file_name),
+        Format::Json => write_grammar_to_json_file(grammar, file_name),
+        Format::Text => write_grammar_to_text_file(grammar, file_name),
+        format @ (Format::Nltk | Format::Bnf) => Err(Error::Format {
+            file: None,
+            position: None,
+            message: format!("grammars cannot be written as {:?}", format),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_is_an_io_error() {
+        match read_grammar_from_ron_file("no/such/grammar.ron") {
+            Err(Error::Io(_)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    /// A new temporary directory for `test`, unique to this process.
+    fn temp_dir(test: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("kxparser-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn text_files_round_trip() {
+        let grammar = crate::parser::example_grammar();
+        let directory = temp_dir("text-files-round-trip");
+        let file_name = directory.join("grammar.txt");
+        let file_name = file_name.to_str().unwrap();
+
+        write_grammar_to_text_file(&grammar, file_name).unwrap();
+        let read = read_grammar_from_text_file(file_name);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(read.unwrap(), grammar);
+    }
+
+    #[test]
+    fn formats_follow_the_extension() {
+        assert_eq!(Format::from_file_name("grammars/english.JSON").unwrap(), Format::Json);
+        assert_eq!(Format::from_file_name("toy.pcfg").unwrap(), Format::Nltk);
+        assert_eq!(
+            Format::from_file_name("grammar").unwrap_err().to_string(),
+            "unknown grammar format for 'grammar'",
+        );
+    }
+
+    #[test]
+    fn grammars_round_trip_through_each_writable_format() {
+        let mut grammar = crate::parser::example_grammar().with_start("NP");
+        grammar.rules[0] = grammar.rules[0].clone().with_weight(0.5);
+        grammar.name = Some("PP attachment".to_string());
+        grammar.description = Some("The grammar of the \"example\" sentences.\nNo empty rules.".to_string());
+        let ebnf = text::parse_grammar(
+            "NP -> Det? Adj+ N (PP | Rel)* [0.5] | N [0.5]\nDet -> \"the\"\nAdj -> \"(\" | \"C++\"\n"
+        ).unwrap();
+        let directory = temp_dir("round-trip-through-each-writable-format");
+        for extension in &["ron", "json", "txt"] {
+            for grammar in [&grammar, &ebnf] {
+                let file_name = directory.join(format!("grammar.{}", extension));
+                let file_name = file_name.to_str().unwrap();
+
+                write_grammar(grammar, file_name).unwrap();
+                let read = read_grammar(file_name);
+
+                assert_eq!(&read.unwrap(), grammar, "{}", extension);
+            }
+        }
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(write_grammar(&grammar, "grammar.bnf").is_err());
+    }
+
+    #[test]
+    fn json_errors_have_a_position() {
+        let directory = temp_dir("json-errors-have-a-position");
+        let file_name = directory.join("malformed.json");
+        let file_name = file_name.to_str().unwrap();
+
+        fs::write(file_name, "[\n  {\"lhs\": \"S\",}\n]").unwrap();
+        let read = read_grammar(file_name);
+        fs::remove_dir_all(&directory).unwrap();
+
+        match read {
+            Err(Error::Format { position: Some((2, _)), .. }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ron_entry_errors_have_the_position_of_the_entry() {
+        let directory = temp_dir("ron-entry-errors-have-the-position-of-the-entry");
+        let file_name = directory.join("typo.ron");
+        let file_name = file_name.to_str().unwrap();
+
+        fs::write(file_name, "[\n  (lhs: \"S\", rhs: [\"NP\"]),\n  (lhs: \"NP\", rsh: [\"lion\"]),\n]").unwrap();
+        let typo = read_grammar(file_name);
+        fs::write(file_name, "(name: \"Toy\", rules: [\n  (lhs: \"S\"),\n])").unwrap();
+        let missing = read_grammar(file_name);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let message = typo.unwrap_err().to_string();
+        assert!(message.contains("typo.ron:3:18: unknown field `rsh`"), "{}", message);
+        assert!(missing.unwrap_err().to_string().ends_with("typo.ron:2:13: missing field `rhs` or `ebnf`"));
+    }
+
+    #[test]
+    fn text_nltk_and_bnf_errors_name_the_file() {
+        let directory = write_files("text-nltk-and-bnf-errors-name-the-file", &[
+            ("x.txt", "S -> NP\nNP Det Noun\n"),
+            ("x.cfg", "S -> NP\nNP Det Noun\n"),
+            ("x.bnf", "S ::= ( NP ]\n"),
+        ]);
+
+        let messages: Vec<String> = ["x.txt", "x.cfg", "x.bnf"].iter()
+            .map(|name| read_grammar(directory.join(name).to_str().unwrap()).unwrap_err().to_string())
+            .collect();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(messages[0].ends_with("x.txt:2:4: expected '->'"), "{}", messages[0]);
+        assert!(messages[1].contains("x.cfg:2"), "{}", messages[1]);
+        assert!(messages[2].ends_with("x.bnf:1:12: expected ')'"), "{}", messages[2]);
+    }
+
+    /// Writes the files of `files` to a new temporary directory.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = temp_dir(test);
+        fs::create_dir_all(directory.join("shared")).unwrap();
+        for (name, content) in files {
+            fs::write(directory.join(name), content).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn includes_are_read_relative_to_the_including_file() {
+        let directory = write_files("includes-relative-to-the-including-file", &[
+            ("main.ron", r#"[
+                (lhs: "S", rhs: ["np::NP", "VP"]),
+                (include: "shared/lexicon.txt"),
+                (include: "np.json", namespace: "np"),
+            ]"#),
+            ("np.json", r#"[{"lhs": "NP", "ebnf": "Det Adj* Noun"}, {"include": "shared/lexicon.txt"}]"#),
+            ("shared/lexicon.txt", "Det -> \"the\"\nNoun -> \"lion\"\nVP -> \"sleeps\"\n"),
+        ]);
+
+        let grammar = read_grammar(directory.join("main.ron").to_str().unwrap());
+        fs::remove_dir_all(&directory).unwrap();
+
+        let rules: Vec<String> = grammar.unwrap().rules.iter().map(|rule| rule.to_string()).collect();
+        assert_eq!(rules, vec!(
+            "S --> np::NP VP",
+            "Det --> the",
+            "Noun --> lion",
+            "VP --> sleeps",
+            "np::NP --> Det Noun [0.5]",
+            "np::NP --> Det np::@Adj+ Noun [0.5]",
+            "np::@Adj+ --> Adj [0.5]",
+            "np::@Adj+ --> np::@Adj+ Adj [0.5]",
+        ));
+    }
+
+    #[test]
+    fn namespaces_apply_to_the_categories_of_the_included_file() {
+        let directory = write_files("namespaces-apply-to-the-included-file", &[
+            ("main.ron", r#"[
+                (lhs: "S", rhs: ["np::NP", "VP"]),
+                (include: "np.ron", namespace: "np"),
+                (include: "shared/lexicon.txt"),
+            ]"#),
+            ("np.ron", r#"[(lhs: "NP", ebnf: "Det Noun (PP | Rel)*"), (include: "shared/lexicon.txt")]"#),
+            ("shared/lexicon.txt", "Det -> \"the\"\nNoun -> \"lion\"\nVP -> \"sleeps\"\n"),
+        ]);
+
+        let grammar = read_grammar(directory.join("main.ron").to_str().unwrap());
+        fs::remove_dir_all(&directory).unwrap();
+
+        let rules: Vec<String> = grammar.unwrap().rules.iter().map(|rule| rule.to_string()).collect();
+        assert_eq!(rules, vec!(
+            "S --> np::NP VP",
+            "np::NP --> Det Noun [0.5]",
+            "np::NP --> Det Noun np::@(PP | Rel)+ [0.5]",
+            "np::@(PP | Rel)+ --> PP [0.25]",
+            "np::@(PP | Rel)+ --> np::@(PP | Rel)+ PP [0.25]",
+            "np::@(PP | Rel)+ --> Rel [0.25]",
+            "np::@(PP | Rel)+ --> np::@(PP | Rel)+ Rel [0.25]",
+            "Det --> the",
+            "Noun --> lion",
+            "VP --> sleeps",
+        ));
+    }
+
+    #[test]
+    fn errors_in_includes_name_the_file_or_the_category() {
+        let directory = write_files("errors-in-includes", &[
+            ("main.ron", r#"[(lhs: "S", rhs: ["np::NP", "np::VP"]), (include: "np.ron", namespace: "np")]"#),
+            ("np.ron", r#"[(lhs: "NP", rhs: ["Det", "Noun"])]"#),
+            ("bad.ron", r#"[(lhs: "S", rhs: ["NP"]), (include: "shared/bad.txt")]"#),
+            ("shared/bad.txt", "S -> NP\nNP Det Noun\n"),
+            ("reserved.ron", r#"[(lhs: "S", rhs: ["@Foo"]), (lhs: "@Foo", rhs: ["a"]), (include: "shared/foo.txt")]"#),
+            ("shared/foo.txt", "\"@Foo\" -> \"b\"\n"),
+        ]);
+
+        let missing = read_grammar(directory.join("main.ron").to_str().unwrap());
+        let bad = read_grammar(directory.join("bad.ron").to_str().unwrap());
+        let reserved = read_grammar(directory.join("reserved.ron").to_str().unwrap());
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(missing.unwrap_err().to_string(), "invalid grammar: no rules for np::VP in its namespace");
+        let message = bad.unwrap_err().to_string();
+        assert!(message.ends_with("shared/bad.txt:2:4: expected '->'"), "{}", message);
+        assert_eq!(
+            reserved.unwrap_err().to_string(),
+            "invalid grammar: rule 1 for @Foo uses the prefix '@' of auxiliary categories"
+        );
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let directory = write_files("include-cycles", &[
+            ("a.ron", r#"[(lhs: "S", rhs: ["x"]), (include: "shared/b.json")]"#),
+            ("shared/b.json", r#"[{"include": "../a.ron"}]"#),
+        ]);
+
+        let read = read_grammar(directory.join("a.ron").to_str().unwrap());
+        fs::remove_dir_all(&directory).unwrap();
+
+        let message = read.unwrap_err().to_string();
+        assert!(message.starts_with("include cycle "), "{}", message);
+        assert!(message.contains("b.json -> ") && message.ends_with("a.ron"), "{}", message);
+    }
+}
@@ -0,0 +1,225 @@
+//! Grammars in the format of NLTK's `.cfg` and `.pcfg` files.
+//!
+//! ```text
+//! % start S
+//! # comments run to the end of the line
+//! S -> NP VP [1.0]
+//! NP -> Det N [0.7] | 'John' [0.3]
+//! ```
+//!
+//! Terminals are quoted with `'` or `"`, and the probabilities of a PCFG
+//! follow each alternative in brackets. The probabilities of the rules for
+//! each category must sum to 1. `% start` sets the start category of the
+//! grammar. Feature grammars and other directives are not supported.
+
+use std::collections::HashMap;
+
+use crate::domain::models::grammar::{Grammar, Rule};
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Symbol(String),
+    Arrow,
+    Bar,
+    Probability(f64),
+}
+
+fn error(line: usize, column: usize, message: &str) -> Error {
+    Error::Format { file: None, position: Some((line, column)), message: message.to_string() }
+}
+
+fn tokenize(line: &str, number: usize) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '#' => break,
+            '|' => {
+                tokens.push((column, Token::Bar));
+                i += 1;
+            }
+            quote @ ('\'' | '"') => {
+                let end = chars[i + 1..].iter().position(|&c| c == quote)
+                    .ok_or_else(|| error(number, column, "unterminated string"))?;
+                tokens.push((column, Token::Symbol(chars[i + 1..i + 1 + end].iter().collect())));
+                i += end + 2;
+            }
+            '[' => {
+                let end = chars[i..].iter().position(|&c| c == ']')
+                    .ok_or_else(|| error(number, column, "missing ']'"))?;
+                let probability: String = chars[i + 1..i + end].iter().collect();
+                let probability = probability.trim().parse()
+                    .map_err(|_| error(number, column, &format!("invalid probability '{}'", probability)))?;
+                tokens.push((column, Token::Probability(probability)));
+                i += end + 1;
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !"|#'\"".contains(chars[i]) {
+                    if chars[i] == '[' {
+                        return Err(error(number, i + 1, "feature structures are not supported"));
+                    }
+                    i += 1;
+                }
+                let symbol: String = chars[start..i].iter().collect();
+                tokens.push((column, if symbol == "->" { Token::Arrow } else { Token::Symbol(symbol) }));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads an NLTK context-free or probabilistic grammar.
+pub fn parse_grammar(text: &str) -> Result<Grammar> {
+    let mut rules = Vec::new();
+    let mut start = None;
+    // whether each rule had a probability, with the line of the rule
+    let mut probabilities = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        if let Some(directive) = line.trim_start().strip_prefix('%') {
+            let mut words = directive.split_whitespace();
+            match (words.next(), words.next(), words.next()) {
+                (Some("start"), Some(symbol), None) => start = Some(symbol.to_string()),
+                _ => return Err(error(number, 1, &format!("unsupported directive '%{}'", directive.trim()))),
+            }
+            continue;
+        }
+        let mut tokens = tokenize(line, number)?.into_iter();
+        let lhs = match tokens.next() {
+            None => continue,
+            Some((_, Token::Symbol(lhs))) => lhs,
+            Some((column, _)) => return Err(error(number, column, "expected a category")),
+        };
+        match tokens.next() {
+            Some((_, Token::Arrow)) => (),
+            Some((column, _)) => return Err(error(number, column, "expected '->'")),
+            None => return Err(error(number, line.trim_end().chars().count() + 1, "expected '->'")),
+        }
+        let mut rhs = Vec::new();
+        let mut probability = None;
+        let end = line.chars().count() + 1;
+        loop {
+            let (column, token) = tokens.next().unwrap_or((end, Token::Bar));
+            if probability.is_some() && token != Token::Bar {
+                return Err(error(number, column, "expected '|' or the end of the line after a probability"));
+            }
+            match token {
+                Token::Bar => {
+                    let rule = Rule::new(&lhs, std::mem::take(&mut rhs));
+                    probabilities.push((probability.is_some(), number));
+                    rules.push(match probability.take() {
+                        Some(probability) => rule.with_weight(probability),
+                        None => rule,
+                    });
+                    if column == end {
+                        break;
+                    }
+                }
+                Token::Symbol(symbol) => rhs.push(symbol),
+                Token::Probability(p) => probability = Some(p),
+                Token::Arrow => return Err(error(number, column, "unexpected '->'")),
+            }
+        }
+    }
+    check_probabilities(&rules, &probabilities)?;
+    if let Some(start) = start {
+        if !rules.iter().any(|rule| rule.lhs() == start) {
+            return Err(Error::Format { file: None, position: None, message: format!("no rules for start category '{}'", start) });
+        }
+        return Ok(Grammar::from_rules(rules).with_start(&start));
+    }
+    Ok(Grammar::from_rules(rules))
+}
+
+/// Checks that either no rule or every rule has a probability, and that
+/// they sum to 1 for each category.
+fn check_probabilities(rules: &[Rule], probabilities: &[(bool, usize)]) -> Result<()> {
+    if probabilities.iter().all(|(given, _)| !given) {
+        return Ok(());
+    }
+    if let Some((_, line)) = probabilities.iter().find(|(given, _)| !given) {
+        return Err(error(*line, 1, "missing probability"));
+    }
+    let mut totals: Vec<(&str, f64)> = Vec::new();
+    let mut index = HashMap::new();
+    for rule in rules {
+        let i = *index.entry(rule.lhs()).or_insert_with(|| {
+            totals.push((rule.lhs(), 0.0));
+            totals.len() - 1
+        });
+        totals[i].1 += rule.weight();
+    }
+    match totals.iter().find(|(_, total)| (total - 1.0).abs() > 1e-6) {
+        Some((lhs, total)) => Err(Error::Format {
+            file: None,
+            position: None,
+            message: format!("probabilities for {} sum to {}", lhs, (total * 1e6).round() / 1e6),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(grammar: &Grammar) -> Vec<String> {
+        grammar.rules.iter().map(Rule::to_string).collect()
+    }
+
+    #[test]
+    fn reads_a_cfg() {
+        let grammar = parse_grammar(
+            "# from the NLTK book\n\
+             S -> NP VP\n\
+             VP -> V NP | V NP PP\n\
+             NP -> \"John\" | Det N\n\
+             Det -> 'a' | 'the'\n"
+        ).unwrap();
+
+        assert_eq!(rules(&grammar), vec!(
+            "S --> NP VP",
+            "VP --> V NP",
+            "VP --> V NP PP",
+            "NP --> John",
+            "NP --> Det N",
+            "Det --> a",
+            "Det --> the",
+        ));
+    }
+
+    #[test]
+    fn reads_a_pcfg_with_a_start_category() {
+        let grammar = parse_grammar(
+            "%start S\n\
+             NP -> Det N [0.7] | 'John' [0.3]\n\
+             S -> NP VP [1.0]\n"
+        ).unwrap();
+
+        assert_eq!(rules(&grammar), vec!("NP --> Det N [0.7]", "NP --> John [0.3]", "S --> NP VP"));
+        assert_eq!(grammar.start(), Some("S"));
+    }
+
+    #[test]
+    fn probabilities_must_be_complete_and_sum_to_one() {
+        let message = |text: &str| parse_grammar(text).unwrap_err().to_string();
+
+        assert_eq!(message("NP -> Det N [0.7] | 'John' [0.2]\n"), "probabilities for NP sum to 0.9");
+        assert_eq!(message("S -> NP VP [1.0]\nNP -> 'John'\n"), "2:1: missing probability");
+    }
+
+    #[test]
+    fn unsupported_constructs_are_errors() {
+        let message = |text: &str| parse_grammar(text).unwrap_err().to_string();
+
+        assert_eq!(message("S -> NP[NUM=?n] VP\n"), "1:8: feature structures are not supported");
+        assert_eq!(message("%include other.cfg\n"), "1:1: unsupported directive '%include other.cfg'");
+        assert_eq!(message("S -> 'John\n"), "1:6: unterminated string");
+        assert_eq!(message("%start VP\nS -> NP\n"), "no rules for start category 'VP'");
+    }
+}
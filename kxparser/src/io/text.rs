@@ -0,0 +1,396 @@
+//! A plain text format for grammars.
+//!
+//! ```text
+//! # comments run to the end of the line
+//! S -> NP VP | VP
+//! NP -> Det Noun [0.8]
+//!     | NP PP [0.2]
+//! Det -> "the" | "a"
+//! ```
+//!
+//! Each line has a rule with alternatives separated by `|`, or continues
+//! the rule of the line before when it starts with `|`. `-->` can be used
+//! instead of `->`. An alternative may end with a weight in brackets and
+//! may be empty, for a rule with an empty right-hand side. Symbols with
+//! spaces or any of `|"#()\` are written in double quotes, with `\"` and
+//! `\\` for quotes and backslashes. Outside quotes, parentheses, `|` inside
+//! them and operators at the end of symbols, as in `Det? Adj* Noun`, are
+//! the EBNF operators of `ebnf`, while a quoted symbol like `"what?"` is
+//! taken literally. Feature structures like `NP[num=?n]` are part of their
+//! symbol.
+//!
+//! Lines starting with `%` set the metadata of the grammar to the rest of
+//! the line: `%start S`, `%name ...`, `%version ...` and
+//! `%description ...`, where each `%description` line adds a line to the
+//! description.
+
+use std::collections::HashSet;
+
+use crate::domain::models::{
+    ebnf,
+    grammar::{Grammar, Rule},
+};
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ebnf(ebnf::Token),
+    Arrow,
+    Weight(f64),
+}
+
+fn error(line: usize, column: usize, message: &str) -> Error {
+    Error::Format { file: None, position: Some((line, column)), message: message.to_string() }
+}
+
+/// Splits a line into tokens with their columns.
+fn tokenize(line: &str, number: usize) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '#' => break,
+            '|' | '(' | ')' => {
+                tokens.push((column, Token::Ebnf(match c {
+                    '|' => ebnf::Token::Bar,
+                    '(' => ebnf::Token::Open,
+                    _ => ebnf::Token::Close,
+                })));
+                i += 1;
+            }
+            '?' | '*' | '+' => {
+                tokens.push((column, Token::Ebnf(ebnf::Token::Operator(c))));
+                i += 1;
+            }
+            '"' => {
+                let mut symbol = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(error(number, column, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => match chars.get(i + 1) {
+                            Some(&escaped) if escaped == '"' || escaped == '\\' => {
+                                symbol.push(escaped);
+                                i += 1;
+                            }
+                            _ => return Err(error(number, i + 1, "unknown escape")),
+                        },
+                        Some(&c) => symbol.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((column, Token::Ebnf(ebnf::Token::Symbol(symbol))));
+            }
+            '[' => {
+                let end = chars[i..].iter().position(|&c| c == ']')
+                    .ok_or_else(|| error(number, column, "missing ']'"))?;
+                let weight: String = chars[i + 1..i + end].iter().collect();
+                let weight = weight.trim().parse()
+                    .map_err(|_| error(number, column, &format!("invalid weight '{}'", weight)))?;
+                tokens.push((column, Token::Weight(weight)));
+                i += end + 1;
+            }
+            _ => {
+                let mut symbol = String::new();
+                while let Some(&c) = chars.get(i) {
+                    if c.is_whitespace() || matches!(c, '|' | '"' | '#' | '(' | ')') {
+                        break;
+                    }
+                    if c == '[' {
+                        let end = chars[i..].iter().position(|&c| c == ']')
+                            .ok_or_else(|| error(number, i + 1, "missing ']'"))?;
+                        symbol.extend(&chars[i..=i + end]);
+                        i += end + 1;
+                        continue;
+                    }
+                    symbol.push(c);
+                    i += 1;
+                }
+                match symbol.as_str() {
+                    "->" | "-->" => tokens.push((column, Token::Arrow)),
+                    _ => tokens.extend(ebnf::split_operators(&symbol, column).into_iter()
+                        .map(|(column, token)| (column, Token::Ebnf(token)))),
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a grammar in the text format, compiling its EBNF operators. The
+/// auxiliary rules come after all others.
+pub fn parse_grammar(text: &str) -> Result<Grammar> {
+    let mut grammar = Grammar::new();
+    let mut rules = Vec::new();
+    let mut auxiliary = Vec::new();
+    let mut lhs: Option<String> = None;
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        if let Some(directive) = line.trim_start().strip_prefix('%') {
+            let (key, value) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+            let value = value.trim().to_string();
+            match key {
+                "start" => grammar.start = Some(value),
+                "name" => grammar.name = Some(value),
+                "version" => grammar.version = Some(value),
+                "description" => {
+                    grammar.description = Some(match grammar.description.take() {
+                        Some(description) => description + "\n" + &value,
+                        None => value,
+                    })
+                }
+                _ => return Err(error(number, 1, &format!("unknown directive '%{}'", key))),
+            }
+            lhs = None;
+            continue;
+        }
+        let tokens = tokenize(line, number)?;
+        let mut tokens = tokens.into_iter();
+        match tokens.next() {
+            None => continue,
+            Some((_, Token::Ebnf(ebnf::Token::Bar))) if lhs.is_some() => (),
+            Some((column, Token::Ebnf(ebnf::Token::Bar))) => {
+                return Err(error(number, column, "'|' without a rule to continue"))
+            }
+            Some((_, Token::Ebnf(ebnf::Token::Symbol(symbol)))) => {
+                match tokens.next() {
+                    Some((_, Token::Arrow)) => (),
+                    Some((column, _)) => return Err(error(number, column, "expected '->'")),
+                    None => return Err(error(number, line.trim_end().chars().count() + 1, "expected '->'")),
+                }
+                lhs = Some(symbol);
+            }
+            Some((column, _)) => return Err(error(number, column, "expected a category")),
+        }
+        let lhs = lhs.as_deref().unwrap();
+        let mut rhs = Vec::new();
+        let mut weight = None;
+        let mut depth = 0;
+        let end = line.chars().count() + 1;
+        loop {
+            let (column, token) = match tokens.next() {
+                Some(next) => next,
+                None => (end, Token::Ebnf(ebnf::Token::Bar)),
+            };
+            if weight.is_some() && token != Token::Ebnf(ebnf::Token::Bar) {
+                return Err(error(number, column, "expected '|' or the end of the line after a weight"));
+            }
+            match token {
+                Token::Ebnf(ebnf::Token::Bar) if depth == 0 || column == end => {
+                    let error = |column, message: &str| error(number, column, message);
+                    for alternative in ebnf::parse(&std::mem::take(&mut rhs), end, error)? {
+                        rules.extend(ebnf::compile(lhs, &alternative, weight.unwrap_or(1.0), &mut auxiliary));
+                    }
+                    weight = None;
+                    if column == end {
+                        break;
+                    }
+                }
+                Token::Ebnf(token) => {
+                    match token {
+                        ebnf::Token::Open => depth += 1,
+                        ebnf::Token::Close if depth > 0 => depth -= 1,
+                        _ => (),
+                    }
+                    rhs.push((column, token));
+                }
+                Token::Weight(w) if depth == 0 => weight = Some(w),
+                Token::Weight(_) => return Err(error(number, column, "weight inside parentheses")),
+                Token::Arrow => return Err(error(number, column, "unexpected '->'")),
+            }
+        }
+    }
+    rules.extend(auxiliary);
+    grammar.rules = rules;
+    Ok(grammar)
+}
+
+/// Whether `symbol` can be written without quotes, i.e. is read back as
+/// the same symbol and not as a directive, an arrow or EBNF operators.
+fn is_bare(symbol: &str) -> bool {
+    let plain = |s: &str| !s.is_empty()
+        && !s.starts_with(['-', '%'])
+        && !s.ends_with(['?', '*', '+'])
+        && !s.contains(|c: char| c.is_whitespace() || "|\"#()\\[]".contains(c));
+    match symbol.split_once('[') {
+        None => plain(symbol),
+        Some((name, features)) => plain(name)
+            && features.ends_with(']')
+            && !features[..features.len() - 1].contains(['[', ']']),
+    }
+}
+
+fn quote(symbol: &str) -> String {
+    format!("\"{}\"", symbol.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes `grammar` in the text format, one line per category in the
+/// order of first appearance, with terminals in quotes.
+pub fn write_grammar(grammar: &Grammar) -> String {
+    let categories: HashSet<&str> = grammar.rules.iter().map(Rule::lhs).collect();
+    let symbol = |symbol: &str| {
+        if categories.contains(symbol) && is_bare(symbol) {
+            symbol.to_string()
+        } else {
+            quote(symbol)
+        }
+    };
+    let mut text = String::new();
+    let metadata = [("name", &grammar.name), ("version", &grammar.version), ("start", &grammar.start)];
+    for (key, value) in metadata.iter() {
+        if let Some(value) = value {
+            text.push_str(&format!("%{} {}\n", key, value));
+        }
+    }
+    for line in grammar.description.iter().flat_map(|description| description.lines()) {
+        text.push_str(&format!("%description {}\n", line));
+    }
+    let mut written = HashSet::new();
+    for rule in &grammar.rules {
+        if !written.insert(rule.lhs()) {
+            continue;
+        }
+        let alternatives: Vec<String> = grammar.rules.iter()
+            .filter(|other| other.lhs() == rule.lhs())
+            .map(|rule| {
+                let mut alternative: Vec<String> = rule.rhs().iter().map(|sym| symbol(sym)).collect();
+                if rule.weight() != 1.0 {
+                    alternative.push(format!("[{}]", rule.weight()));
+                }
+                alternative.join(" ")
+            })
+            .collect();
+        text.push_str(&format!("{} -> {}\n", symbol(rule.lhs()), alternatives.join(" | ")).replace(" \n", "\n"));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::example_grammar;
+
+    #[test]
+    fn parse_rules_with_alternatives_weights_and_comments() {
+        let grammar = parse_grammar(
+            "# a small grammar\n\
+             S -> NP VP | VP   # imperatives\n\
+             \n\
+             NP --> Det Noun [0.8]\n\
+             \x20   | NP PP [0.2]\n\
+             Det -> \"the\" | \"a \\\"b\\\"\"\n"
+        ).unwrap();
+
+        let rules: Vec<String> = grammar.rules.iter().map(Rule::to_string).collect();
+        assert_eq!(rules, vec!(
+            "S --> NP VP",
+            "S --> VP",
+            "NP --> Det Noun [0.8]",
+            "NP --> NP PP [0.2]",
+            "Det --> the",
+            "Det --> a \"b\"",
+        ));
+    }
+
+    #[test]
+    fn parse_ebnf_and_features() {
+        let grammar = parse_grammar("NP[num=?n, per=3] -> Det? Adj[deg=?d]* (PP | Rel)+ | \n").unwrap();
+
+        let rules: Vec<String> = grammar.rules.iter().map(Rule::to_string).collect();
+        assert_eq!(rules, vec!(
+            "NP[num=?n, per=3] --> @(PP | Rel)+ [0.25]",
+            "NP[num=?n, per=3] --> @Adj[deg=?d]+ @(PP | Rel)+ [0.25]",
+            "NP[num=?n, per=3] --> Det @(PP | Rel)+ [0.25]",
+            "NP[num=?n, per=3] --> Det @Adj[deg=?d]+ @(PP | Rel)+ [0.25]",
+            "NP[num=?n, per=3] --> ",
+            "@Adj[deg=?d]+ --> Adj[deg=?d] [0.5]",
+            "@Adj[deg=?d]+ --> @Adj[deg=?d]+ Adj[deg=?d] [0.5]",
+            "@(PP | Rel)+ --> PP [0.25]",
+            "@(PP | Rel)+ --> @(PP | Rel)+ PP [0.25]",
+            "@(PP | Rel)+ --> Rel [0.25]",
+            "@(PP | Rel)+ --> @(PP | Rel)+ Rel [0.25]",
+        ));
+    }
+
+    #[test]
+    fn directives_set_the_metadata() {
+        let grammar = parse_grammar(
+            "%name Toy grammar\n\
+             %description Two lines\n\
+             %description of description.\n\
+             %start VP\n\
+             VP -> \"sleeps\"\n"
+        ).unwrap();
+
+        assert_eq!(grammar.name.as_deref(), Some("Toy grammar"));
+        assert_eq!(grammar.description.as_deref(), Some("Two lines\nof description."));
+        assert_eq!(grammar.start(), Some("VP"));
+        assert_eq!(
+            write_grammar(&grammar),
+            "%name Toy grammar\n%start VP\n%description Two lines\n%description of description.\nVP -> \"sleeps\"\n",
+        );
+        assert_eq!(parse_grammar("%author me\n").unwrap_err().to_string(), "1:1: unknown directive '%author'");
+    }
+
+    #[test]
+    fn errors_have_line_and_column() {
+        let position = |text: &str| match parse_grammar(text) {
+            Err(Error::Format { position, .. }) => position,
+            other => panic!("unexpected result {:?}", other),
+        };
+
+        assert_eq!(position("S -> NP VP\nNP Det Noun\n"), Some((2, 4)));
+        assert_eq!(position("S -> NP [x]\n"), Some((1, 9)));
+        assert_eq!(position("S -> NP [1] VP\n"), Some((1, 13)));
+        assert_eq!(position("  | NP\n"), Some((1, 3)));
+        assert_eq!(position("S -> \"NP\n"), Some((1, 6)));
+        assert_eq!(position("S -> ( NP | VP\n"), Some((1, 15)));
+        assert_eq!(position("S -> NP )\n"), Some((1, 9)));
+        assert_eq!(position("S\n"), Some((1, 2)));
+    }
+
+    #[test]
+    fn write_groups_alternatives_and_quotes_terminals() {
+        let mut grammar = parse_grammar("S -> NP VP [0.5] | VP [0.5]\nVP -> \"sees\" | \"says \\\"hi\\\"\"\nNP ->\n").unwrap();
+        grammar.rules.push(Rule::new("(A | B)", vec!("Adj+".to_string())));
+
+        assert_eq!(
+            write_grammar(&grammar),
+            "S -> NP VP [0.5] | VP [0.5]\nVP -> \"sees\" | \"says \\\"hi\\\"\"\nNP ->\n\"(A | B)\" -> \"Adj+\"\n",
+        );
+    }
+
+    #[test]
+    fn punctuation_and_operators_round_trip_as_symbols() {
+        let rhs = |symbols: &[&str]| symbols.iter().map(|sym| sym.to_string()).collect();
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("S", rhs(&["(", "Q?", ")", "|", "Adj+"])),
+            Rule::new("Q?", rhs(&["what?", "C++", "#", "%start"])),
+            Rule::new("Adj+", rhs(&["*", "@"])),
+            Rule::new("%start", Vec::new()),
+        ));
+        let text = write_grammar(&grammar);
+
+        assert_eq!(text.lines().next(), Some("S -> \"(\" \"Q?\" \")\" \"|\" \"Adj+\""));
+        assert_eq!(parse_grammar(&text).unwrap(), grammar);
+        assert_eq!(parse_grammar("S -> \"(\" NP \")\" \"what?\"\n").unwrap().rules[0].rhs(), &["(", "NP", ")", "what?"]);
+    }
+
+    #[test]
+    fn grammars_round_trip() {
+        let mut grammar = example_grammar();
+        grammar.rules.push(Rule::new("NP[num=?n]", vec!("Det[num=?n]".to_string(), "[odd".to_string())));
+        grammar.rules.push(Rule::new("Empty", Vec::new()).with_weight(0.125));
+
+        assert_eq!(parse_grammar(&write_grammar(&grammar)).unwrap(), grammar);
+    }
+}
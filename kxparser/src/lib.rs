@@ -1,4 +1,10 @@
 pub mod domain;
+pub mod error;
+pub mod generator;
+pub mod io;
+pub mod parser;
+
+pub use error::{Error, Result};
 
 #[cfg(test)]
 mod tests {
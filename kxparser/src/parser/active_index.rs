@@ -0,0 +1,51 @@
+/// Active edges of a chart, indexed by position and next symbol.
+///
+/// The entries of each position are sorted by key and stored after the
+/// previous positions in one vector, so looking up the edges at position
+/// `k` waiting for a symbol is a binary search in `offsets[k]..offsets[k+1]`.
+pub(crate) struct ActiveIndex<K, E> {
+    entries: Vec<(K, E)>,
+    offsets: Vec<usize>,
+}
+
+impl<K: Ord + Copy, E> ActiveIndex<K, E> {
+    pub(crate) fn new() -> Self {
+        ActiveIndex { entries: Vec::new(), offsets: Vec::new() }
+    }
+
+    /// Adds the next position, draining its entries from `position`.
+    pub(crate) fn push_position(&mut self, position: &mut Vec<(K, E)>) {
+        position.sort_by_key(|(key, _)| *key);
+        self.offsets.push(self.entries.len());
+        self.entries.append(position);
+    }
+
+    /// The entries at position `k` with the given key.
+    pub(crate) fn get(&self, k: usize, key: K) -> &[(K, E)] {
+        let end = self.offsets.get(k + 1).copied().unwrap_or(self.entries.len());
+        let entries = &self.entries[self.offsets[k]..end];
+        let from = entries.partition_point(|(other, _)| *other < key);
+        let to = entries.partition_point(|(other, _)| *other <= key);
+        &entries[from..to]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_returns_entries_with_key_at_position() {
+        let mut index = ActiveIndex::new();
+        index.push_position(&mut vec!((2, 'a'), (1, 'b'), (2, 'c')));
+        index.push_position(&mut Vec::new());
+        index.push_position(&mut vec!((1, 'd')));
+
+        let values = |k, key| index.get(k, key).iter().map(|(_, v)| *v).collect::<Vec<char>>();
+        assert_eq!(values(0, 2), vec!('a', 'c'));
+        assert_eq!(values(0, 1), vec!('b'));
+        assert_eq!(values(0, 3), vec!());
+        assert_eq!(values(1, 1), vec!());
+        assert_eq!(values(2, 1), vec!('d'));
+    }
+}
@@ -0,0 +1,266 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::domain::models::{
+    chart::Chart,
+    edge::Edge,
+    grammar::{Grammar, Rule},
+};
+use crate::parser::{active_index::ActiveIndex, leftcorners_dict, nullable::Nullable, Parser};
+
+/// Left-corner Earley parser with a priority agenda and a beam.
+///
+/// Edges are scored by a figure of merit, their inside log weight divided
+/// by the number of words they span, and the agenda of each position pops
+/// the best edge first. At most `beam_width` edges built from rules are
+/// kept per position, and edges scoring more than `threshold` below the
+/// best edge of their position are dropped. Without limits it builds the
+/// same chart as `Earley2`; with them it may miss parses, but the work per
+/// position is bounded. Skipping a symbol that derives the empty string
+/// leaves the score of an edge as it is.
+///
+/// Edges with the same merit are popped wider ones first, and then in the
+/// order they were found. This decides what the beam keeps when the
+/// weights don't, e.g. with the default weights of 1, which give every
+/// edge a merit of 0 so that the threshold never prunes anything.
+pub struct BeamEarley<'g> {
+    start: Option<&'g str>,
+    leftcorners: HashMap<&'g str, Vec<&'g Rule>>,
+    nullable: Nullable<'g>,
+    beam_width: Option<usize>,
+    threshold: Option<f64>,
+}
+
+/// An agenda entry: an edge with its inside log weight, and the number of
+/// edges pushed before it.
+#[derive(Clone, Copy, Debug)]
+struct Scored<'a> {
+    merit: f64,
+    inside: f64,
+    edge: Edge<'a>,
+    order: usize,
+}
+
+/// The agenda of a position, popping the best edge first.
+#[derive(Default)]
+struct Agenda<'a> {
+    heap: BinaryHeap<Scored<'a>>,
+    pushed: usize,
+}
+
+impl<'a> Agenda<'a> {
+    fn push(&mut self, inside: f64, edge: Edge<'a>) {
+        let merit = inside / (edge.end() - edge.start()) as f64;
+        self.heap.push(Scored { merit, inside, edge, order: self.pushed });
+        self.pushed += 1;
+    }
+
+    fn pop(&mut self) -> Option<Scored<'a>> {
+        self.heap.pop()
+    }
+}
+
+impl PartialEq for Scored<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored<'_> {}
+
+impl PartialOrd for Scored<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let width = |scored: &Self| scored.edge.end() - scored.edge.start();
+        self.merit.total_cmp(&other.merit)
+            .then_with(|| width(self).cmp(&width(other)))
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+impl<'g> Parser<'g> for BeamEarley<'g> {
+    fn from_grammar(grammar: &'g Grammar) -> Self {
+        BeamEarley {
+            start: grammar.start(),
+            leftcorners: leftcorners_dict(&grammar.rules),
+            nullable: Nullable::new(grammar),
+            beam_width: None,
+            threshold: None,
+        }
+    }
+
+    fn start(&self) -> Option<&'g str> {
+        self.start
+    }
+
+    fn parse<'a>(&'a self, input: &[&'a str]) -> Chart<'a> {
+        let mut result = Chart::with_capacity(input.len() + 1, 0);
+        result.add_position();
+        self.nullable.empty_edges(0).for_each(|edge| result.add_edge(edge));
+        let mut active_index: ActiveIndex<&str, (Edge, f64)> = ActiveIndex::new();
+        let mut active = Vec::new();
+        active_index.push_position(&mut active);
+        let mut edgeset = HashSet::new();
+
+        for (k, sym) in input.iter().enumerate() {
+            let k = k + 1;
+            result.add_position();
+            edgeset.clear();
+            let mut kept = 0;
+            let mut best = f64::NEG_INFINITY;
+
+            // Scan
+            let mut agenda = Agenda::default();
+            agenda.push(0.0, Edge::new(k-1, k, sym, None, 0));
+
+            while let Some(Scored { merit, inside, edge, .. }) = agenda.pop() {
+                let is_word = edge.rhs().is_empty();
+                if !is_word {
+                    if self.beam_width.is_some_and(|width| kept >= width) {
+                        continue;
+                    }
+                    if self.threshold.is_some_and(|threshold| merit < best - threshold) {
+                        continue;
+                    }
+                }
+                if edgeset.insert(edge) {
+                    if !is_word {
+                        kept += 1;
+                        best = best.max(merit);
+                    }
+                    match edge.next_symbol() {
+                        None => {
+                            // Predict
+                            if let Some(rules) = self.leftcorners.get(edge.lhs()) {
+                                for rule in rules {
+                                    let predicted = Edge::from_rule(edge.start(), k, rule, 1);
+                                    agenda.push(inside + rule.weight().ln(), predicted);
+                                }
+                            }
+                            for &(rule, dot) in self.nullable.leftcorners(edge.lhs()) {
+                                let predicted = Edge::from_rule(edge.start(), k, rule, dot);
+                                agenda.push(inside + rule.weight().ln(), predicted);
+                            }
+
+                            // Complete
+                            for (_, (e, score)) in active_index.get(edge.start(), edge.lhs()) {
+                                agenda.push(score + inside, e.advance(k));
+                            }
+                            result.add_edge(edge);
+                        }
+                        Some(next) => {
+                            if self.nullable.is_nullable(next) {
+                                agenda.push(inside, edge.advance(k));
+                            }
+                            active.push((next, (edge, inside)));
+                        }
+                    }
+                }
+            }
+            self.nullable.empty_edges(k).for_each(|edge| result.add_edge(edge));
+            active_index.push_position(&mut active);
+        }
+        result
+    }
+}
+
+impl BeamEarley<'_> {
+    /// Keeps at most `width` edges built from rules at each position.
+    pub fn with_beam_width(mut self, width: usize) -> Self {
+        self.beam_width = Some(width);
+        self
+    }
+
+    /// Drops edges whose figure of merit is more than `threshold` below
+    /// the best one at their position.
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{example, example_grammar, success, Earley2};
+
+    fn edges(chart: &Chart) -> Vec<String> {
+        let mut edges: Vec<String> = chart.edges().iter().map(|e| e.to_string()).collect();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn without_limits_builds_the_earley2_chart() {
+        let grammar = example_grammar();
+        let sentence = example(3);
+
+        let beam = BeamEarley::from_grammar(&grammar);
+        let earley2 = Earley2::from_grammar(&grammar);
+        let beam = beam.parse(&sentence);
+        let earley2 = earley2.parse(&sentence);
+
+        assert_eq!(edges(&beam), edges(&earley2));
+    }
+
+    #[test]
+    fn beam_width_bounds_the_edges_per_position() {
+        let grammar = example_grammar();
+        let sentence = example(3);
+        let exact = BeamEarley::from_grammar(&grammar);
+        let parser = BeamEarley::from_grammar(&grammar).with_beam_width(4);
+
+        let chart = parser.parse(&sentence);
+
+        assert!(chart.chartsize() < exact.parse(&sentence).chartsize());
+        // the word itself is always kept
+        assert!((1..chart.len()).all(|k| chart.edgeset(k).len() <= 5));
+    }
+
+    #[test]
+    fn threshold_prunes_unlikely_edges() {
+        let mut grammar = example_grammar();
+        for rule in &mut grammar.rules {
+            if rule.lhs() == "NP" && rule.rhs()[0] == "NP" {
+                *rule = rule.clone().with_weight(0.001);
+            }
+        }
+        let sentence = example(1);
+
+        let exact = BeamEarley::from_grammar(&grammar);
+        let pruned = BeamEarley::from_grammar(&grammar).with_threshold(2.0);
+        let exact = exact.parse(&sentence);
+        let pruned = pruned.parse(&sentence);
+
+        let np_pp = |chart: &Chart| chart.edges().iter()
+            .any(|e| e.lhs() == "NP" && e.rhs()[0] == "NP");
+        assert!(np_pp(&exact));
+        assert!(!np_pp(&pruned));
+        assert!(success(&pruned, "S", 0));
+    }
+
+    #[test]
+    fn equal_merits_prefer_wider_edges_and_then_earlier_ones() {
+        let rule = |lhs: &str, rhs: &[&str]| Rule::new(lhs, rhs.iter().map(|sym| sym.to_string()).collect());
+        let grammar = Grammar::from_rules(vec!(
+            rule("A", &["a"]),
+            rule("Y", &["A", "b"]),
+            rule("X", &["b"]),
+            rule("Z", &["b"]),
+        ));
+        // every edge has merit 0, and at position 2 the edges for X, Z and
+        // Y compete for two places, in that order
+        let parser = BeamEarley::from_grammar(&grammar).with_beam_width(2);
+
+        let chart = parser.parse(&["a", "b"]);
+
+        let passive: Vec<&str> = chart.edgeset(2).iter().filter(|e| e.is_passive()).map(|e| e.lhs()).collect();
+        assert!(passive.contains(&"Y") && passive.contains(&"X"), "{:?}", passive);
+        assert!(!passive.contains(&"Z"), "{:?}", passive);
+    }
+}
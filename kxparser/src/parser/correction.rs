@@ -0,0 +1,391 @@
+use std::fmt;
+
+use crate::domain::models::{
+    compiled_grammar::{CompiledGrammar, RuleId, Symbol},
+    grammar::Grammar,
+    tree::Tree,
+};
+use crate::error::{Error, Result};
+
+/// The cost of each kind of edit, which must be finite and not negative.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Costs {
+    pub insertion: f64,
+    pub deletion: f64,
+    pub substitution: f64,
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Costs { insertion: 1.0, deletion: 1.0, substitution: 1.0 }
+    }
+}
+
+impl Costs {
+    /// Checks that every cost is finite and not negative.
+    pub fn validate(&self) -> Result<()> {
+        let costs = [("insertion", self.insertion), ("deletion", self.deletion), ("substitution", self.substitution)];
+        match costs.iter().find(|(_, cost)| !cost.is_finite() || *cost < 0.0) {
+            Some(&(edit, cost)) => Err(Error::InvalidCost { edit, cost }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A change to the input, with positions in the original input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit<'a> {
+    /// Insert `word` before the word in `position`.
+    Insert { position: usize, word: &'a str },
+    /// Remove the word in `position`.
+    Delete { position: usize, word: &'a str },
+    /// Replace the word `from` in `position` with `to`.
+    Substitute { position: usize, from: &'a str, to: &'a str },
+}
+
+impl fmt::Display for Edit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Insert { position, word } => write!(f, "{}: insert '{}'", position, word),
+            Edit::Delete { position, word } => write!(f, "{}: delete '{}'", position, word),
+            Edit::Substitute { position, from, to } => {
+                write!(f, "{}: replace '{}' with '{}'", position, from, to)
+            }
+        }
+    }
+}
+
+/// The cheapest way to turn the input into a goal.
+#[derive(Clone, Debug)]
+pub struct Correction<'a> {
+    pub cost: f64,
+    /// The parse tree of the corrected input, without the auxiliary
+    /// categories of EBNF rules.
+    pub tree: Tree,
+    pub edits: Vec<Edit<'a>>,
+}
+
+/// An error-correcting parser.
+///
+/// For every symbol and span of the input it finds the cheapest way to
+/// derive the span from the symbol when words may be inserted, deleted
+/// or substituted. A terminal covering a span either matches (or
+/// substitutes) one of its words or is inserted, and the other words of
+/// the span are deleted. The table is filled by increasing span length,
+/// repeating each span until no cost improves, since rules whose other
+/// symbols are all inserted make a symbol depend on others over the same
+/// span. Only the terminals that occur in the input have their own costs;
+/// all others cost the same, the cheapest insertion or substitution. This
+/// takes `O(n^4)` time in the length of the input, which is fine for
+/// checking single sentences.
+pub struct Corrector {
+    compiled: CompiledGrammar,
+    costs: Costs,
+}
+
+#[derive(Clone, Debug)]
+enum Back {
+    /// A terminal matching or substituting the word in the position, or
+    /// inserted if `None`; all other words of the span are deleted.
+    Terminal(Option<usize>),
+    /// A rule, with the positions between the symbols of its right-hand side.
+    Rule(RuleId, Vec<usize>),
+}
+
+struct Table {
+    n: usize,
+    /// The column of each symbol in `cells`, or `None` for the terminals
+    /// that are not in the input.
+    columns: Vec<Option<usize>>,
+    width: usize,
+    cells: Vec<(f64, Option<Back>)>,
+    /// For each span, the cheapest terminal that is not in the input.
+    other: Vec<(f64, Back)>,
+}
+
+impl Table {
+    fn span(&self, i: usize, j: usize) -> usize {
+        i * (self.n + 1) + j
+    }
+
+    fn cell(&self, sym: Symbol, i: usize, j: usize) -> (f64, Option<&Back>) {
+        match self.columns[sym] {
+            Some(column) => {
+                let (cost, back) = &self.cells[self.span(i, j) * self.width + column];
+                (*cost, back.as_ref())
+            }
+            None => {
+                let (cost, back) = &self.other[self.span(i, j)];
+                (*cost, Some(back))
+            }
+        }
+    }
+
+    fn cost(&self, sym: Symbol, i: usize, j: usize) -> f64 {
+        self.cell(sym, i, j).0
+    }
+
+    fn back(&self, sym: Symbol, i: usize, j: usize) -> Option<&Back> {
+        self.cell(sym, i, j).1
+    }
+
+    /// Sets the cost of a symbol with a column.
+    fn set(&mut self, sym: Symbol, i: usize, j: usize, cost: f64, back: Back) {
+        if let Some(column) = self.columns[sym] {
+            let index = self.span(i, j) * self.width + column;
+            self.cells[index] = (cost, Some(back));
+        }
+    }
+}
+
+impl Corrector {
+    pub fn from_grammar(grammar: &Grammar) -> Self {
+        Corrector { compiled: CompiledGrammar::compile(grammar), costs: Costs::default() }
+    }
+
+    /// Uses `costs` for the edits, or fails if one of them is negative or
+    /// not finite.
+    pub fn with_costs(mut self, costs: Costs) -> Result<Self> {
+        costs.validate()?;
+        self.costs = costs;
+        Ok(self)
+    }
+
+    /// Finds the cheapest correction of `input` into a `goal`, or `None`
+    /// if the goal is unknown or derives nothing.
+    pub fn correct<'a>(&'a self, goal: &str, input: &[&'a str]) -> Option<Correction<'a>> {
+        let goal = self.compiled.symbols().get(goal)?;
+        let n = input.len();
+        let compiled = &self.compiled;
+        let mut words = Vec::new();
+        for &word in input {
+            if let Some(sym) = compiled.symbols().get(word).filter(|&sym| compiled.is_terminal(sym)) {
+                if !words.contains(&sym) {
+                    words.push(sym);
+                }
+            }
+        }
+        let mut width = 0;
+        let columns = (0..compiled.symbols().len())
+            .map(|sym| {
+                let column = (!compiled.is_terminal(sym) || words.contains(&sym)).then_some(width);
+                width += column.is_some() as usize;
+                column
+            })
+            .collect();
+        let spans = (n + 1) * (n + 1);
+        let mut table = Table {
+            n,
+            columns,
+            width,
+            cells: vec!((f64::INFINITY, None); spans * width),
+            other: vec!((f64::INFINITY, Back::Terminal(None)); spans),
+        };
+        for len in 0..=n {
+            for i in 0..=(n - len) {
+                self.fill(&mut table, input, &words, i, i + len);
+            }
+        }
+        let cost = table.cost(goal, 0, n);
+        if cost.is_infinite() {
+            return None;
+        }
+        let mut edits = Vec::new();
+        let tree = self.build(&table, input, goal, 0, n, &mut edits).flatten();
+        Some(Correction { cost, tree, edits })
+    }
+
+    fn fill(&self, table: &mut Table, input: &[&str], words: &[Symbol], i: usize, j: usize) {
+        let compiled = &self.compiled;
+        let costs = &self.costs;
+        let deleted = |words: usize| words as f64 * costs.deletion;
+        let mut other = (costs.insertion + deleted(j - i), Back::Terminal(None));
+        if j > i && costs.substitution + deleted(j - i - 1) < other.0 {
+            other = (costs.substitution + deleted(j - i - 1), Back::Terminal(Some(i)));
+        }
+        let span = table.span(i, j);
+        table.other[span] = other;
+        for &sym in words {
+            let word = compiled.symbols().name(sym);
+            let mut best = (costs.insertion + deleted(j - i), Back::Terminal(None));
+            for (k, &token) in input.iter().enumerate().take(j).skip(i) {
+                let matched = if token == word { 0.0 } else { costs.substitution };
+                let cost = matched + deleted(j - i - 1);
+                if cost < best.0 {
+                    best = (cost, Back::Terminal(Some(k)));
+                }
+            }
+            table.set(sym, i, j, best.0, best.1);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (id, rule) in compiled.rules().iter().enumerate() {
+                if let Some((cost, splits)) = self.best_split(table, rule.rhs(), i, j) {
+                    if cost < table.cost(rule.lhs(), i, j) {
+                        table.set(rule.lhs(), i, j, cost, Back::Rule(id, splits));
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The cheapest way for `rhs` to cover `i`-`j`, with the positions
+    /// between its symbols.
+    fn best_split(&self, table: &Table, rhs: &[Symbol], i: usize, j: usize) -> Option<(f64, Vec<usize>)> {
+        let width = j - i + 1;
+        // best[t][q - i]: the cost of rhs[..t] covering i-q, and the
+        // position where rhs[t-1] starts
+        let mut best = vec!(vec!((f64::INFINITY, 0); width); rhs.len() + 1);
+        best[0][0] = (0.0, i);
+        for (t, &sym) in rhs.iter().enumerate() {
+            for q in i..=j {
+                for p in i..=q {
+                    let cost = best[t][p - i].0 + table.cost(sym, p, q);
+                    if cost < best[t + 1][q - i].0 {
+                        best[t + 1][q - i] = (cost, p);
+                    }
+                }
+            }
+        }
+        let cost = best[rhs.len()][j - i].0;
+        if cost.is_infinite() {
+            return None;
+        }
+        let mut splits = vec!(j);
+        let mut q = j;
+        for t in (1..=rhs.len()).rev() {
+            q = best[t][q - i].1;
+            splits.push(q);
+        }
+        splits.reverse();
+        Some((cost, splits))
+    }
+
+    fn build<'a>(
+        &'a self,
+        table: &Table,
+        input: &[&'a str],
+        sym: Symbol,
+        i: usize,
+        j: usize,
+        edits: &mut Vec<Edit<'a>>,
+    ) -> Tree {
+        let name = self.compiled.symbols().name(sym);
+        match table.back(sym, i, j) {
+            Some(Back::Rule(rule, splits)) => {
+                let children = self.compiled.rule(*rule).rhs().iter()
+                    .zip(splits.windows(2))
+                    .map(|(&child, span)| self.build(table, input, child, span[0], span[1], edits))
+                    .collect();
+                Tree::new(name, children)
+            }
+            Some(Back::Terminal(matched)) => {
+                if matched.is_none() {
+                    edits.push(Edit::Insert { position: i, word: name });
+                }
+                for (k, &word) in input.iter().enumerate().take(j).skip(i) {
+                    if Some(k) != *matched {
+                        edits.push(Edit::Delete { position: k, word });
+                    } else if word != name {
+                        edits.push(Edit::Substitute { position: k, from: word, to: name });
+                    }
+                }
+                Tree::leaf(name)
+            }
+            None => Tree::leaf(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::example_grammar;
+
+    fn correct(input: &[&str]) -> (f64, String, Vec<String>) {
+        let grammar = example_grammar();
+        let corrector = Corrector::from_grammar(&grammar);
+        let correction = corrector.correct("S", input).unwrap();
+        (
+            correction.cost,
+            correction.tree.to_string(),
+            correction.edits.iter().map(Edit::to_string).collect(),
+        )
+    }
+
+    #[test]
+    fn correct_sentence_needs_no_edits() {
+        let (cost, tree, edits) = correct(&["the", "lion", "sees"]);
+
+        assert_eq!(cost, 0.0);
+        assert_eq!(tree, "(S (NP (Det the) (Noun lion)) (VP (Verb sees)))");
+        assert!(edits.is_empty());
+    }
+
+    #[test]
+    fn missing_word_is_inserted() {
+        let (cost, tree, edits) = correct(&["the", "lion", "sees", "zebra"]);
+
+        assert_eq!(cost, 1.0);
+        assert_eq!(tree, "(S (NP (Det the) (Noun lion)) (VP (Verb sees) (NP (Det the) (Noun zebra))))");
+        assert_eq!(edits, vec!("3: insert 'the'"));
+    }
+
+    #[test]
+    fn extra_word_is_deleted() {
+        let (cost, _, edits) = correct(&["the", "lion", "sees", "sees", "a", "zebra"]);
+
+        assert_eq!(cost, 1.0);
+        assert_eq!(edits.len(), 1);
+        assert!(edits[0].ends_with("delete 'sees'"));
+    }
+
+    #[test]
+    fn wrong_word_is_substituted() {
+        let (cost, _, edits) = correct(&["the", "lion", "sea", "a", "zebra"]);
+
+        assert_eq!(cost, 1.0);
+        assert_eq!(edits, vec!("2: replace 'sea' with 'sees'"));
+    }
+
+    #[test]
+    fn costs_decide_between_edits() {
+        let grammar = example_grammar();
+        let corrector = Corrector::from_grammar(&grammar)
+            .with_costs(Costs { insertion: 5.0, deletion: 1.0, substitution: 5.0 })
+            .unwrap();
+        let correction = corrector.correct("S", &["the", "lion", "sees", "zebra"]).unwrap();
+
+        assert_eq!(correction.cost, 1.0);
+        assert_eq!(correction.edits, vec!(Edit::Delete { position: 3, word: "zebra" }));
+    }
+
+    #[test]
+    fn costs_must_be_finite_and_not_negative() {
+        let grammar = example_grammar();
+        let with_costs = |costs| Corrector::from_grammar(&grammar).with_costs(costs).err().map(|e| e.to_string());
+
+        assert_eq!(with_costs(Costs { insertion: -1.0, ..Costs::default() }).unwrap(), "invalid insertion cost -1");
+        assert_eq!(with_costs(Costs { substitution: f64::NAN, ..Costs::default() }).unwrap(), "invalid substitution cost NaN");
+        assert_eq!(with_costs(Costs { deletion: 0.0, ..Costs::default() }), None);
+    }
+
+    #[test]
+    fn empty_input_is_filled_in() {
+        let (cost, tree, edits) = correct(&[]);
+
+        assert_eq!(cost, 3.0);
+        assert_eq!(tree, "(S (NP (Det the) (Noun zebra)) (VP (Verb sees)))");
+        assert_eq!(edits.len(), 3);
+    }
+
+    #[test]
+    fn unknown_goal_has_no_correction() {
+        let grammar = example_grammar();
+        let corrector = Corrector::from_grammar(&grammar);
+
+        assert!(corrector.correct("Utterance", &["the"]).is_none());
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+
+use crate::domain::models::edge::Edge;
+
+/// Where and why a parse failed, see `Session::diagnose`.
+#[derive(Debug)]
+pub struct Diagnosis<'a> {
+    /// The number of words that could be read as the start of a goal.
+    pub position: usize,
+    /// The word after them, or `None` if the input ended too early.
+    pub token: Option<&'a str>,
+    /// The active edges waiting in `position`.
+    pub stuck: Vec<Edge<'a>>,
+    /// The symbols those edges wait for.
+    pub expected: Vec<&'a str>,
+}
+
+impl fmt::Display for Diagnosis<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.token {
+            Some(token) => write!(f, "unexpected '{}' at position {}", token, self.position)?,
+            None => write!(f, "unexpected end of input at position {}", self.position)?,
+        }
+        if !self.expected.is_empty() {
+            write!(f, ", expected {}", self.expected.join(" or "))?;
+        }
+        for edge in &self.stuck {
+            write!(f, "\n    {}", edge)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_diagnosis() {
+        let rhs = vec!(String::from("Prep"), String::from("NP"));
+        let diagnosis = Diagnosis {
+            position: 6,
+            token: Some("under"),
+            stuck: vec!(Edge::new(5, 6, "PP", Some(&rhs), 1)),
+            expected: vec!("NP"),
+        };
+
+        assert_eq!(
+            diagnosis.to_string(),
+            "unexpected 'under' at position 6, expected NP\n    [5-6: PP --> Prep . NP]"
+        );
+    }
+
+    #[test]
+    fn format_diagnosis_at_end_of_input() {
+        let diagnosis = Diagnosis { position: 2, token: None, stuck: Vec::new(), expected: vec!("PP", "VP") };
+
+        assert_eq!(diagnosis.to_string(), "unexpected end of input at position 2, expected PP or VP");
+    }
+}
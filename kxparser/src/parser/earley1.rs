@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+use crate::domain::models::{
+    chart::Chart,
+    edge::Edge,
+    grammar::Grammar,
+};
+use crate::parser::{nullable::Nullable, Parser};
+
+/// The basic Earley parser, trying every grammar rule when predicting.
+pub struct Earley1<'g> {
+    grammar: &'g Grammar,
+    nullable: Nullable<'g>,
+}
+
+impl<'g> Parser<'g> for Earley1<'g> {
+    fn from_grammar(grammar: &'g Grammar) -> Self {
+        Earley1 { grammar, nullable: Nullable::new(grammar) }
+    }
+
+    fn parse<'a>(&'a self, input: &[&'a str]) -> Chart<'a> {
+        earley1(self.grammar, &self.nullable, input)
+    }
+
+    fn start(&self) -> Option<&'g str> {
+        self.grammar.start()
+    }
+}
+
+fn earley1<'a>(grammar: &'a Grammar, nullable: &Nullable<'a>, input: &[&'a str]) -> Chart<'a> {
+    let mut result = Chart::with_capacity(input.len() + 1, 0);
+    result.add_position();
+    nullable.empty_edges(0).for_each(|edge| result.add_edge(edge));
+    // The active edges, by end position
+    let mut chart: Vec<Vec<Edge>> = vec!(Vec::new());
+
+    for (k, word) in input.iter().enumerate() {
+        let k = k + 1;
+        result.add_position();
+        let mut edgeset = HashSet::new();
+        let mut active = Vec::new();
+
+        // Scan
+        let mut agenda = vec!(Edge::new(k-1, k, word, None, 0));
+        while let Some(edge) = agenda.pop() {
+            if edgeset.insert(edge) {
+                if edge.is_passive() {
+                    // Predict
+                    for rule in &grammar.rules {
+                        for (i, sym) in rule.rhs().iter().enumerate() {
+                            if sym == edge.lhs() {
+                                agenda.push(Edge::from_rule(edge.start(), k, rule, i + 1));
+                            } // if
+                            if !nullable.is_nullable(sym) {
+                                break;
+                            }
+                        } // for sym
+                    } // for
+
+                    // Complete
+                    for e in &chart[edge.start()] {
+                        if e.next_symbol() == Some(edge.lhs()) {
+                            agenda.push(e.advance(k));
+                        }
+                    }
+                    result.add_edge(edge);
+                } else {
+                    if edge.next_symbol().is_some_and(|next| nullable.is_nullable(next)) {
+                        agenda.push(edge.advance(k));
+                    }
+                    active.push(edge);
+                } // if edge.is_passive
+            } // if edgeset.insert
+
+        } // while let Some(edge) = agenda.pop()
+        nullable.empty_edges(k).for_each(|edge| result.add_edge(edge));
+        chart.push(active);
+    } // for k, word in input
+    result
+}
@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::models::{
+    chart::Chart,
+    edge::Edge,
+    grammar::{Grammar, Rule},
+};
+use crate::parser::{active_index::ActiveIndex, leftcorners_dict, nullable::Nullable, Parser};
+
+/// Earley parser that predicts from a precomputed left-corner table and
+/// indexes the active edges by their next symbol.
+pub struct Earley2<'g> {
+    start: Option<&'g str>,
+    leftcorners: HashMap<&'g str, Vec<&'g Rule>>,
+    nullable: Nullable<'g>,
+}
+
+impl<'g> Parser<'g> for Earley2<'g> {
+    fn from_grammar(grammar: &'g Grammar) -> Self {
+        Earley2 {
+            start: grammar.start(),
+            leftcorners: leftcorners_dict(&grammar.rules),
+            nullable: Nullable::new(grammar),
+        }
+    }
+
+    fn parse<'a>(&'a self, input: &[&'a str]) -> Chart<'a> {
+        earley2(&self.leftcorners, &self.nullable, input)
+    }
+
+    fn start(&self) -> Option<&'g str> {
+        self.start
+    }
+}
+
+fn earley2<'a>(
+    leftcorners: &HashMap<&'a str, Vec<&'a Rule>>,
+    nullable: &Nullable<'a>,
+    input: &[&'a str],
+) -> Chart<'a> {
+    let mut result = Chart::with_capacity(input.len() + 1, 0);
+    result.add_position();
+    nullable.empty_edges(0).for_each(|edge| result.add_edge(edge));
+    let mut active_index: ActiveIndex<&str, Edge> = ActiveIndex::new();
+    let mut active = Vec::new();
+    active_index.push_position(&mut active);
+    let mut edgeset = HashSet::new();
+
+    for (k, sym) in input.iter().enumerate() {
+        let k = k + 1;
+        result.add_position();
+        edgeset.clear();
+
+        // Scan
+        let mut agenda = vec!(Edge::new(k-1, k, sym, None, 0));
+
+        while let Some(edge) = agenda.pop() {
+            if edgeset.insert(edge) {
+                match edge.next_symbol() {
+                    None => {
+                        // Predict
+                        if let Some(rules) = leftcorners.get(edge.lhs()) {
+                            for rule in rules {
+                                agenda.push(Edge::from_rule(edge.start(), k, rule, 1));
+                            }
+                        }
+                        for &(rule, dot) in nullable.leftcorners(edge.lhs()) {
+                            agenda.push(Edge::from_rule(edge.start(), k, rule, dot));
+                        }
+
+                        // Complete
+                        for (_, e) in active_index.get(edge.start(), edge.lhs()) {
+                            agenda.push(e.advance(k));
+                        }
+                        result.add_edge(edge);
+                    }
+                    Some(next) => {
+                        if nullable.is_nullable(next) {
+                            agenda.push(edge.advance(k));
+                        }
+                        active.push((next, edge));
+                    }
+                }
+            } // if edge not in edgeset
+        } // while agenda
+        nullable.empty_edges(k).for_each(|edge| result.add_edge(edge));
+        active_index.push_position(&mut active);
+    } // for input
+    result
+}
@@ -0,0 +1,198 @@
+use crate::domain::models::{
+    chart::Chart,
+    compiled_grammar::CompiledGrammar,
+    grammar::Grammar,
+};
+use crate::parser::{Limits, LimitExceeded, Parser, Session};
+
+/// Earley parser working on a `CompiledGrammar`.
+///
+/// Edges are stored as `(rule, dot, start)` triples of integers, so
+/// nothing is allocated when predicting or completing and hashing an edge
+/// never compares strings. Passive items are added to the chart as string
+/// edges as soon as they are found. A `Session` parses incrementally,
+/// one word at a time.
+///
+/// Rules with an empty right-hand side are supported: an edge waiting for
+/// a symbol deriving the empty string is also moved past it, and each
+/// position gets empty edges for the categories deriving the empty string,
+/// so `Chart::recognizes` can tell that a goal derives empty input.
+///
+/// With `with_leo(true)` the parser uses Leo's optimization: when a
+/// passive edge can only be completed along a deterministic chain of
+/// right-recursive rules, it jumps straight to the topmost edge of the
+/// chain instead of building every edge on the way. Right-recursive
+/// grammars are then parsed in linear time, but the intermediate passive
+/// edges of such chains are left out of the chart. The optimization is not
+/// used for grammars with rules deriving the empty string, which are
+/// parsed as without it.
+pub struct Earley3<'g> {
+    pub(super) grammar: &'g Grammar,
+    pub(super) compiled: CompiledGrammar,
+    pub(super) leo: bool,
+}
+
+impl<'g> Parser<'g> for Earley3<'g> {
+    fn from_grammar(grammar: &'g Grammar) -> Self {
+        Earley3 { grammar, compiled: CompiledGrammar::compile(grammar), leo: false }
+    }
+
+    fn parse<'a>(&'a self, input: &[&'a str]) -> Chart<'a> {
+        let mut session = Session::new(self, None);
+        for word in input {
+            session.push(word);
+        }
+        session.into_chart()
+    }
+
+    fn start(&self) -> Option<&'g str> {
+        self.grammar.start()
+    }
+}
+
+impl Earley3<'_> {
+    /// Turns Leo's optimization on or off. It stays off for a grammar with
+    /// rules deriving the empty string.
+    pub fn with_leo(mut self, leo: bool) -> Self {
+        self.leo = leo;
+        self
+    }
+
+    /// Starts an incremental parse looking for a `goal` from position 0.
+    pub fn session<'a>(&'a self, goal: &str) -> Session<'a> {
+        Session::new(self, Some(goal))
+    }
+
+    /// Parses `input` within `limits`, returning the partial chart if one
+    /// of them is exceeded.
+    pub fn parse_with_limits<'a>(&'a self, input: &[&'a str], limits: Limits) -> Result<Chart<'a>, LimitExceeded<'a>> {
+        let mut session = Session::new(self, None).with_limits(limits);
+        for word in input {
+            if let Err(limit) = session.try_push(word) {
+                return Err(LimitExceeded { limit, chart: session.into_chart() });
+            }
+        }
+        Ok(session.into_chart())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        CancellationToken, Limit, example, example_grammar, right_recursive_example, right_recursive_grammar, success,
+    };
+
+    #[test]
+    fn leo_recognizes_right_recursion_with_a_linear_chart() {
+        let grammar = right_recursive_grammar();
+        let plain = Earley3::from_grammar(&grammar);
+        let leo = Earley3::from_grammar(&grammar).with_leo(true);
+
+        let small = right_recursive_example(10);
+        let large = right_recursive_example(20);
+        let plain_small = plain.parse(&small);
+        let plain_large = plain.parse(&large);
+        let leo_small = leo.parse(&small);
+        let leo_large = leo.parse(&large);
+
+        assert!(success(&plain_large, "S", 0));
+        assert!(success(&leo_large, "S", 0));
+        assert!(plain_large.chartsize() > 3 * plain_small.chartsize());
+        assert!(leo_large.chartsize() <= 2 * leo_small.chartsize() + 10);
+    }
+
+    #[test]
+    fn leo_rejects_incomplete_sentences() {
+        let grammar = right_recursive_grammar();
+        let leo = Earley3::from_grammar(&grammar).with_leo(true);
+
+        let input = right_recursive_example(3);
+        assert!(success(&leo.parse(&input[..6]), "S", 0));
+        assert!(!success(&leo.parse(&input[..8]), "S", 0));
+        assert!(!success(&leo.parse(&input[..7]), "S", 0));
+    }
+
+    #[test]
+    fn leo_keeps_the_chart_of_the_example_grammar() {
+        let grammar = example_grammar();
+        let plain = Earley3::from_grammar(&grammar);
+        let leo = Earley3::from_grammar(&grammar).with_leo(true);
+        let sentence = example(3);
+
+        assert_eq!(leo.parse(&sentence).chartsize(), plain.parse(&sentence).chartsize());
+    }
+
+    #[test]
+    fn parse_within_limits_returns_the_chart() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let sentence = example(3);
+        let limits = Limits::new().with_max_edges(10_000).with_max_agenda_pushes(10_000);
+
+        let chart = parser.parse_with_limits(&sentence, limits).unwrap();
+
+        assert_eq!(chart.chartsize(), parser.parse(&sentence).chartsize());
+    }
+
+    #[test]
+    fn edge_limit_returns_the_partial_chart() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let sentence = example(30);
+
+        let exceeded = parser.parse_with_limits(&sentence, Limits::new().with_max_edges(500)).unwrap_err();
+
+        assert_eq!(exceeded.limit, Limit::Edges);
+        assert!(exceeded.chart.len() < sentence.len() + 1);
+        assert!(exceeded.chart.chartsize() <= 500);
+    }
+
+    #[test]
+    fn cancelled_parse_stops_before_the_first_word() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let exceeded = parser.parse_with_limits(&example(3), Limits::new().with_cancellation(token)).unwrap_err();
+
+        assert_eq!(exceeded.limit, Limit::Cancelled);
+        assert_eq!(exceeded.chart.len(), 1);
+        assert_eq!(exceeded.to_string(), "parse stopped in position 0: cancelled");
+    }
+
+    fn optional_determiners() -> Grammar {
+        let mut grammar = example_grammar();
+        grammar.rules.push(crate::domain::models::grammar::Rule::new("Det", Vec::new()));
+        grammar.rules.push(crate::domain::models::grammar::Rule::new("VP", Vec::new()));
+        grammar
+    }
+
+    #[test]
+    fn empty_rules_are_skipped() {
+        let grammar = optional_determiners();
+        let parser = Earley3::from_grammar(&grammar);
+
+        let chart = parser.parse(&["lion", "sees", "zebra", "in", "park"]);
+
+        assert!(chart.recognizes("S", 0, -1));
+        assert!(chart.recognizes("NP", 0, 1));
+        assert!(chart.recognizes("NP", 2, 3));
+        assert!(chart.recognizes("S", 0, 1));
+        assert!(chart.recognizes("VP", 3, 3));
+    }
+
+    #[test]
+    fn goal_deriving_the_empty_string_recognizes_empty_input() {
+        let grammar = optional_determiners();
+        let parser = Earley3::from_grammar(&grammar);
+        let chart = parser.parse(&[]);
+
+        assert!(!chart.recognizes("S", 0, -1));
+        assert!(chart.recognizes("VP", 0, -1));
+        assert!(chart.recognizes("Det", 0, 0));
+        assert!(parser.session("VP").is_complete());
+        assert!(!parser.session("S").is_complete());
+    }
+}
@@ -0,0 +1,167 @@
+use std::cmp::Ordering;
+
+use crate::domain::models::{
+    chart::Chart,
+    grammar::Grammar,
+    tree::Tree,
+};
+use crate::parser::TreeBuilder;
+
+/// How to choose between covers of the input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Cover {
+    /// As few fragments as possible, then the highest weight.
+    FewestPieces,
+    /// The highest product of fragment weights, then as few fragments as
+    /// possible.
+    HighestWeight,
+}
+
+#[derive(Clone, Copy)]
+struct Score {
+    /// Words not covered by any grammar category.
+    bare: usize,
+    pieces: usize,
+    weight: f64,
+}
+
+impl Score {
+    fn cmp(&self, other: &Score, cover: Cover) -> Ordering {
+        let pieces = self.pieces.cmp(&other.pieces);
+        let weight = other.weight.total_cmp(&self.weight);
+        self.bare.cmp(&other.bare).then(match cover {
+            Cover::FewestPieces => pieces.then(weight),
+            Cover::HighestWeight => weight.then(pieces),
+        })
+    }
+}
+
+/// Covers the input from 0 to the end with passive edges of the chart.
+///
+/// Returns the trees of the fragments in order. Words that no grammar
+/// category covers become leaves, and covers with fewer such words are
+/// always preferred; among the rest `cover` decides. A sentence that
+/// parses as a single category gives a single tree.
+pub fn fragments(grammar: &Grammar, chart: &Chart, cover: Cover) -> Vec<Tree> {
+    if chart.is_empty() {
+        return Vec::new();
+    }
+    let mut builder = TreeBuilder::new(grammar, chart);
+    // best[k]: the score of the best cover of 0-k, and its last fragment
+    let mut best: Vec<Option<(Score, usize, Tree)>> = vec!(None; chart.len());
+    let zero = Score { bare: 0, pieces: 0, weight: 0.0 };
+    for k in 1..chart.len() {
+        let mut spans: Vec<(&str, usize, bool)> = chart.edgeset(k).iter()
+            .filter(|edge| edge.is_passive())
+            .map(|edge| (edge.lhs(), edge.start(), edge.rhs().is_empty()))
+            .collect();
+        spans.sort_unstable();
+        spans.dedup();
+        for (lhs, start, bare) in spans {
+            let before = match start {
+                0 => zero,
+                _ => match &best[start] {
+                    Some((score, _, _)) => *score,
+                    None => continue,
+                },
+            };
+            if let Some((weight, tree)) = builder.weighted_tree(lhs, start, k) {
+                let score = Score {
+                    bare: before.bare + bare as usize,
+                    pieces: before.pieces + 1,
+                    weight: before.weight + weight,
+                };
+                let better = match &best[k] {
+                    Some((current, _, _)) => score.cmp(current, cover) == Ordering::Less,
+                    None => true,
+                };
+                if better {
+                    best[k] = Some((score, start, tree));
+                }
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut k = chart.len().saturating_sub(1);
+    while k > 0 {
+        match best[k].take() {
+            Some((_, start, tree)) => {
+                result.push(tree);
+                k = start;
+            }
+            None => break,
+        }
+    }
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{example_grammar, Earley3, Parser};
+
+    fn strings(trees: &[Tree]) -> Vec<String> {
+        trees.iter().map(Tree::to_string).collect()
+    }
+
+    #[test]
+    fn parsable_sentence_is_one_fragment() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let chart = parser.parse(&["the", "lion", "sees"]);
+
+        assert_eq!(
+            strings(&fragments(&grammar, &chart, Cover::FewestPieces)),
+            vec!("(S (NP (Det the) (Noun lion)) (VP (Verb sees)))")
+        );
+    }
+
+    #[test]
+    fn unparsable_sentence_is_covered_by_fragments() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let chart = parser.parse(&["the", "lion", "the", "zebra", "under", "unicorn"]);
+
+        assert_eq!(
+            strings(&fragments(&grammar, &chart, Cover::FewestPieces)),
+            vec!(
+                "(NP (Det the) (Noun lion))",
+                "(NP (Det the) (Noun zebra))",
+                "(Prep under)",
+                "unicorn",
+            )
+        );
+    }
+
+    #[test]
+    fn highest_weight_prefers_probable_fragments() {
+        let mut grammar = example_grammar();
+        for rule in &mut grammar.rules {
+            if rule.lhs() == "NP" {
+                *rule = rule.clone().with_weight(0.5);
+            }
+        }
+        let parser = Earley3::from_grammar(&grammar);
+        let chart = parser.parse(&["the", "lion", "the"]);
+
+        assert_eq!(
+            strings(&fragments(&grammar, &chart, Cover::FewestPieces)),
+            vec!("(NP (Det the) (Noun lion))", "(Det the)")
+        );
+        assert_eq!(
+            strings(&fragments(&grammar, &chart, Cover::HighestWeight)),
+            vec!("(Det the)", "(Noun lion)", "(Det the)")
+        );
+    }
+
+    #[test]
+    fn empty_input_has_no_fragments() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let chart = parser.parse(&[]);
+
+        assert!(fragments(&grammar, &chart, Cover::FewestPieces).is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use crate::domain::models::chart::Chart;
+
+/// How often the clock and the cancellation token are looked at, in
+/// agenda items.
+pub(crate) const CHECK_INTERVAL: usize = 1024;
+
+/// Bounds on the work done by a parse. The default has no bounds.
+///
+/// Only `Earley3::parse_with_limits` and its sessions take limits; the
+/// other parsers are bounded by nothing but the beam of `BeamEarley`.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub max_edges: Option<usize>,
+    pub max_agenda_pushes: Option<usize>,
+    pub deadline: Option<Instant>,
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Limits::default()
+    }
+
+    /// Limits the number of edges, passive and active, over all positions.
+    pub fn with_max_edges(mut self, max_edges: usize) -> Self {
+        self.max_edges = Some(max_edges);
+        self
+    }
+
+    /// Limits the number of items put on the agenda over all positions.
+    pub fn with_max_agenda_pushes(mut self, max_agenda_pushes: usize) -> Self {
+        self.max_agenda_pushes = Some(max_agenda_pushes);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline `timeout` from now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// The limit exceeded by `edges` and `pushes`, or by the time.
+    pub(crate) fn check(&self, edges: usize, pushes: usize, clock: bool) -> Result<(), Limit> {
+        if self.max_edges.is_some_and(|max| edges > max) {
+            return Err(Limit::Edges);
+        }
+        if self.max_agenda_pushes.is_some_and(|max| pushes > max) {
+            return Err(Limit::AgendaPushes);
+        }
+        if clock {
+            if self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                return Err(Limit::Cancelled);
+            }
+            if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(Limit::Deadline);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A flag shared between a parse and whoever wants to stop it, possibly
+/// from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// The limit that stopped a parse.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Limit {
+    Edges,
+    AgendaPushes,
+    Deadline,
+    Cancelled,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Edges => write!(f, "edge limit exceeded"),
+            Limit::AgendaPushes => write!(f, "agenda limit exceeded"),
+            Limit::Deadline => write!(f, "deadline passed"),
+            Limit::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// A parse stopped by a limit, with the chart built until then.
+///
+/// The last position of the chart may be incomplete.
+#[derive(Debug)]
+pub struct LimitExceeded<'a> {
+    pub limit: Limit,
+    pub chart: Chart<'a>,
+}
+
+impl fmt::Display for LimitExceeded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "parse stopped in position {}: {}", self.chart.len().saturating_sub(1), self.limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_limits_never_stop() {
+        assert_eq!(Limits::new().check(usize::MAX, usize::MAX, true), Ok(()));
+    }
+
+    #[test]
+    fn check_reports_the_exceeded_limit() {
+        let token = CancellationToken::new();
+        let limits = Limits::new()
+            .with_max_edges(10)
+            .with_max_agenda_pushes(20)
+            .with_cancellation(token.clone());
+
+        assert_eq!(limits.check(10, 20, true), Ok(()));
+        assert_eq!(limits.check(11, 0, true), Err(Limit::Edges));
+        assert_eq!(limits.check(0, 21, true), Err(Limit::AgendaPushes));
+        token.cancel();
+        assert_eq!(limits.check(0, 0, false), Ok(()));
+        assert_eq!(limits.check(0, 0, true), Err(Limit::Cancelled));
+    }
+
+    #[test]
+    fn passed_deadline_stops() {
+        let limits = Limits::new().with_deadline(Instant::now());
+
+        assert_eq!(limits.check(0, 0, true), Err(Limit::Deadline));
+    }
+}
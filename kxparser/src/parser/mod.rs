@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use crate::domain::models::{
+    chart::Chart,
+    grammar::{Grammar, Rule},
+};
+
+mod active_index;
+mod beam;
+mod correction;
+mod diagnosis;
+mod earley1;
+mod earley2;
+mod earley3;
+mod fragments;
+mod limits;
+mod nullable;
+mod session;
+mod stolcke;
+mod trees;
+mod unification;
+
+pub use beam::BeamEarley;
+pub use correction::{Correction, Corrector, Costs, Edit};
+pub use diagnosis::Diagnosis;
+pub use earley1::Earley1;
+pub use earley2::Earley2;
+pub use earley3::Earley3;
+pub use fragments::{fragments, Cover};
+pub use limits::{CancellationToken, Limit, LimitExceeded, Limits};
+pub use session::Session;
+pub use stolcke::{Stolcke, StolckeSession};
+pub use trees::TreeBuilder;
+pub use unification::{FeatureChart, FeatureEarley, FeatureEdge};
+
+/// A chart parsing algorithm.
+///
+/// A parser is built once from a `Grammar`, doing whatever precomputation
+/// the algorithm needs, and can then parse any number of sentences.
+pub trait Parser<'g> {
+    fn from_grammar(grammar: &'g Grammar) -> Self where Self: Sized;
+
+    fn parse<'a>(&'a self, input: &[&'a str]) -> Chart<'a>;
+
+    /// The start category of the grammar, see `Grammar::start`, or `None`
+    /// for a grammar without rules.
+    fn start(&self) -> Option<&'g str>;
+
+    /// Whether `input` is a sentence of the start category.
+    fn recognize<'a>(&'a self, input: &[&'a str]) -> bool {
+        self.start().is_some_and(|start| success(&self.parse(input), start, 0))
+    }
+}
+
+const EXAMPLE_PREFIX: [&str; 5] = [
+    "the",
+    "lion",
+    "sees",
+    "a",
+    "zebra",
+];
+const EXAMPLE_SUFFIX: [&str; 9] =  [
+    "under",
+    "a",
+    "tree",
+    "with",
+    "a",
+    "telescope",
+    "in",
+    "the",
+    "park",
+];
+
+const EXAMPLE_RULES: [(&str, &[&str]); 18] = [
+    ("S", &["NP", "VP"]),
+    ("VP", &["Verb"]),
+    ("VP", &["Verb", "NP"]),
+    ("VP", &["VP", "PP"]),
+    ("NP", &["Det", "Noun"]),
+    ("NP", &["NP", "PP"]),
+    ("PP", &["Prep", "NP"]),
+    ("Verb", &["sees"]),
+    ("Det", &["the"]),
+    ("Det", &["a"]),
+    ("Prep", &["under"]),
+    ("Prep", &["with"]),
+    ("Prep", &["in"]),
+    ("Noun", &["zebra"]),
+    ("Noun", &["lion"]),
+    ("Noun", &["tree"]),
+    ("Noun", &["park"]),
+    ("Noun", &["telescope"]),
+];
+
+/// The example sentence with `n` prepositional phrases attached.
+pub fn example(n: usize) -> Vec<&'static str> {
+    EXAMPLE_PREFIX.iter()
+        .chain(EXAMPLE_SUFFIX.iter().cycle().take(n*3))
+        .copied()
+        .collect()
+}
+
+/// The grammar that covers the `example` sentences.
+pub fn example_grammar() -> Grammar {
+    grammar_from(&EXAMPLE_RULES)
+}
+
+const RIGHT_RECURSIVE_RULES: [(&str, &[&str]); 7] = [
+    ("S", &["NP", "VP"]),
+    ("VP", &["Verb"]),
+    ("VP", &["Verb", "S"]),
+    ("NP", &["Det", "Noun"]),
+    ("Det", &["the"]),
+    ("Noun", &["lion"]),
+    ("Verb", &["sees"]),
+];
+
+fn grammar_from(rules: &[(&str, &[&str])]) -> Grammar {
+    Grammar::from_rules(
+        rules.iter()
+            .map(|(lhs, rhs)| Rule::new(lhs, rhs.iter().map(|x| x.to_string()).collect()))
+            .collect()
+    )
+}
+
+/// "the lion sees" repeated `n` times, a right-branching sentence.
+pub fn right_recursive_example(n: usize) -> Vec<&'static str> {
+    ["the", "lion", "sees"].iter().cycle().take(n*3).copied().collect()
+}
+
+/// A grammar where `S` and `VP` are right recursive through `VP -> Verb S`.
+pub fn right_recursive_grammar() -> Grammar {
+    grammar_from(&RIGHT_RECURSIVE_RULES)
+}
+
+pub fn leftcorners_dict(grammar: &[Rule]) -> HashMap<&str, Vec<&Rule>> {
+    let mut leftcorners = HashMap::new();
+    for rule in grammar {
+        if let Some(first) = rule.rhs().first() {
+            leftcorners.entry(first.as_str()).or_insert_with(Vec::new).push(rule);
+        }
+    }
+    leftcorners
+}
+
+/// Whether `cat` spans from `start` to the end of the input.
+pub fn success(chart: &Chart, cat: &str, start: usize) -> bool {
+    chart.recognizes(cat, start, -1)
+}
+
+/// Parses `sentence`, tells whether it is a `cat` and prints the edges
+/// ending in `positions`.
+pub fn test<'a, 'g: 'a>(
+    parser: &'a impl Parser<'g>,
+    cat: &str,
+    sentence: &[&'a str],
+    positions: &[i32],
+    ) {
+    let nwords = sentence.len();
+    if nwords <= 15 {
+        println!("Parsing {} words: {}", sentence.len(), sentence.join(" "));
+    } else {
+        println!(
+            "Parsing {} words: {} ... {}",
+            sentence.len(),
+            sentence[..3].join(" "),
+            sentence[(nwords-9)..].join(" "),
+        );
+    }
+    let chart = parser.parse(sentence);
+    if success(&chart, cat, 0) {
+        println!("Yay, success!!");
+    } else {
+        println!("Meh, failure :(");
+    }
+    print_chart(&chart, positions, None);
+}
+
+pub fn print_chart(chart: &Chart, positions: &[i32], cutoff: Option<usize>) {
+    let cutoff: usize = cutoff.unwrap_or(8);
+    println!("Chart size: {} edges", chart.chartsize());
+    for (k, edgeset) in chart.edgesets().enumerate() {
+        if !edgeset.is_empty() && (positions.contains(&(k as i32)) || positions.contains(&(k as i32 - chart.len() as i32))) {
+            println!("{} edges ending in position {}:", edgeset.len(), k);
+            let mut sorted_edgeset = edgeset.to_vec();
+            sorted_edgeset.sort();
+            for (n, edge) in sorted_edgeset.iter().enumerate() {
+                if cutoff > 0 && n >= cutoff {
+                    println!("    ...");
+                    break;
+                }
+                println!("    {}", edge);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_has_three_words_per_pp() {
+        assert_eq!(example(0), vec!("the", "lion", "sees", "a", "zebra"));
+        assert_eq!(example(1).len(), 8);
+        assert_eq!(example(4).len(), 17);
+        assert_eq!(&example(4)[14..], &["under", "a", "tree"]);
+    }
+
+    #[test]
+    fn leftcorners_dict_groups_rules_by_first_rhs_symbol() {
+        let grammar = example_grammar();
+        let leftcorners = leftcorners_dict(&grammar.rules);
+
+        assert_eq!(leftcorners["NP"].len(), 2);
+        assert_eq!(leftcorners["Verb"].len(), 2);
+        assert_eq!(leftcorners["a"].len(), 1);
+        assert!(!leftcorners.contains_key("S"));
+    }
+
+    #[test]
+    fn parsers_recognize_the_start_category() {
+        let grammar = example_grammar();
+        let np = example_grammar().with_start("NP");
+        let parsers: [(Box<dyn Parser>, Box<dyn Parser>); 3] = [
+            (Box::new(Earley1::from_grammar(&grammar)), Box::new(Earley1::from_grammar(&np))),
+            (Box::new(Earley2::from_grammar(&grammar)), Box::new(Earley2::from_grammar(&np))),
+            (Box::new(Earley3::from_grammar(&grammar)), Box::new(Earley3::from_grammar(&np))),
+        ];
+
+        for (s, np) in &parsers {
+            assert_eq!((s.start(), np.start()), (Some("S"), Some("NP")));
+            assert!(s.recognize(&example(1)));
+            assert!(!s.recognize(&["the", "lion"]));
+            assert!(np.recognize(&["the", "lion"]));
+        }
+    }
+
+    fn sorted_chart(chart: Chart) -> Vec<Vec<String>> {
+        chart.edgesets()
+            .map(|edges| {
+                let mut edges: Vec<String> = edges.iter().map(|e| e.to_string()).collect();
+                edges.sort();
+                edges
+            })
+            .collect()
+    }
+
+    #[test]
+    fn all_parsers_build_the_same_chart() {
+        let grammar = example_grammar();
+        let earley1 = Earley1::from_grammar(&grammar);
+        let earley2 = Earley2::from_grammar(&grammar);
+        let earley3 = Earley3::from_grammar(&grammar);
+        for n in 0..4 {
+            let sentence = example(n);
+            let chart1 = earley1.parse(&sentence);
+            let chart2 = earley2.parse(&sentence);
+            let chart3 = earley3.parse(&sentence);
+
+            assert!(success(&chart1, "S", 0));
+            assert!(success(&chart2, "S", 0));
+            assert!(success(&chart3, "S", 0));
+            let expected = sorted_chart(chart1);
+            assert_eq!(sorted_chart(chart2), expected);
+            assert_eq!(sorted_chart(chart3), expected);
+        }
+    }
+
+    #[test]
+    fn empty_rules_and_empty_input_do_not_panic() {
+        let mut grammar = example_grammar();
+        grammar.rules.push(Rule::new("NP", Vec::new()));
+        let earley1 = Earley1::from_grammar(&grammar);
+        let earley2 = Earley2::from_grammar(&grammar);
+        let earley3 = Earley3::from_grammar(&grammar);
+
+        for chart in [earley1.parse(&[]), earley2.parse(&[]), earley3.parse(&[])] {
+            assert_eq!(chart.len(), 1);
+            assert!(!success(&chart, "S", 0));
+        }
+        assert!(success(&earley1.parse(&example(0)), "S", 0));
+        assert!(!success(&Chart::new(), "S", 0));
+    }
+
+    #[test]
+    fn all_parsers_handle_empty_rules() {
+        let mut grammar = example_grammar();
+        grammar.rules.push(Rule::new("Det", Vec::new()));
+        grammar.rules.push(Rule::new("VP", vec!("Verb".to_string(), "Adv".to_string())));
+        grammar.rules.push(Rule::new("Adv", Vec::new()));
+        grammar.rules.push(Rule::new("Adv", vec!("now".to_string())));
+        let earley1 = Earley1::from_grammar(&grammar);
+        let earley2 = Earley2::from_grammar(&grammar);
+        let earley3 = Earley3::from_grammar(&grammar);
+        let beam = BeamEarley::from_grammar(&grammar);
+
+        let sentences: [&[&str]; 4] = [&["lion", "sees"], &["the", "lion", "sees", "now"], &["lion", "sees", "zebra"], &[]];
+        for sentence in sentences {
+            let expected = sorted_chart(earley3.parse(sentence));
+            assert_eq!(sorted_chart(earley1.parse(sentence)), expected);
+            assert_eq!(sorted_chart(earley2.parse(sentence)), expected);
+            assert_eq!(sorted_chart(beam.parse(sentence)), expected);
+            assert_eq!(earley1.recognize(sentence), !sentence.is_empty());
+        }
+    }
+
+    #[test]
+    fn unknown_word_gets_only_a_scan_edge() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let chart = parser.parse(&["the", "unicorn"]);
+
+        assert_eq!(chart.edgeset(2).len(), 1);
+        assert!(!success(&chart, "S", 0));
+    }
+
+    #[test]
+    fn incomplete_sentence_fails() {
+        let grammar = example_grammar();
+        let sentence = example(1);
+        let parser = Earley2::from_grammar(&grammar);
+        let chart = parser.parse(&sentence[..6]);
+
+        assert!(!success(&chart, "S", 0));
+    }
+
+    #[test]
+    fn parser_can_be_reused_for_many_sentences() {
+        let grammar = example_grammar();
+        let parser = Earley2::from_grammar(&grammar);
+        let sentences: Vec<Vec<&str>> = (0..3).map(example).collect();
+
+        for sentence in &sentences {
+            assert!(success(&parser.parse(sentence), "S", 0));
+        }
+    }
+}
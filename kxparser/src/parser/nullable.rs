@@ -0,0 +1,79 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::models::{compiled_grammar::CompiledGrammar, edge::Edge, grammar::{Grammar, Rule}};
+
+/// The rules with an empty right-hand side, as the left-corner parsers
+/// need them.
+///
+/// A symbol after a sequence of nullable symbols, i.e. symbols that derive
+/// the empty string, is a left corner of its rule as well, and an active
+/// edge waiting for a nullable symbol can also skip it. The passive edges
+/// of the rules deriving the empty string are added to every position, so
+/// that empty spans are recognized.
+pub(crate) struct Nullable<'g> {
+    symbols: HashSet<&'g str>,
+    leftcorners: HashMap<&'g str, Vec<(&'g Rule, usize)>>,
+    rules: Vec<&'g Rule>,
+}
+
+impl<'g> Nullable<'g> {
+    /// Takes the nullable symbols and rules from the compiled grammar, whose
+    /// rules are numbered as in `grammar`.
+    pub(crate) fn new(grammar: &'g Grammar) -> Self {
+        let compiled = CompiledGrammar::compile(grammar);
+        let rules: Vec<&Rule> = compiled.nullable_rules().iter().map(|&id| &grammar.rules[id]).collect();
+        let symbols = rules.iter().map(|rule| rule.lhs()).collect();
+        let mut leftcorners: HashMap<&str, Vec<(&Rule, usize)>> = HashMap::new();
+        for symbol in 0..compiled.symbols().len() {
+            for &(id, dot) in compiled.nullable_leftcorners(symbol) {
+                let rule = &grammar.rules[id];
+                leftcorners.entry(rule.rhs()[dot - 1].as_str()).or_default().push((rule, dot));
+            }
+        }
+        Nullable { symbols, leftcorners, rules }
+    }
+
+    pub(crate) fn is_nullable(&self, symbol: &str) -> bool {
+        self.symbols.contains(symbol)
+    }
+
+    /// The rules where `symbol` comes after a non-empty sequence of
+    /// nullable symbols, with the position after `symbol`.
+    pub(crate) fn leftcorners(&self, symbol: &str) -> &[(&'g Rule, usize)] {
+        self.leftcorners.get(symbol).map_or(&[], Vec::as_slice)
+    }
+
+    /// The passive edges over the empty span in position `k`.
+    pub(crate) fn empty_edges(&self, k: usize) -> impl Iterator<Item = Edge<'g>> + '_ {
+        self.rules.iter().map(move |rule| Edge::from_rule(k, k, rule, rule.rhs().len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(lhs: &str, rhs: &[&str]) -> Rule {
+        Rule::new(lhs, rhs.iter().map(|sym| sym.to_string()).collect())
+    }
+
+    #[test]
+    fn finds_nullable_symbols_and_their_leftcorners() {
+        let grammar = Grammar::from_rules(vec!(
+            rule("S", &["NP", "VP"]),
+            rule("NP", &["Det", "Noun"]),
+            rule("Det", &[]),
+            rule("Noun", &["lion"]),
+            rule("Opt", &["Det", "Det"]),
+        ));
+        let nullable = Nullable::new(&grammar);
+
+        assert!(nullable.is_nullable("Det") && nullable.is_nullable("Opt"));
+        assert!(!nullable.is_nullable("NP") && !nullable.is_nullable("lion"));
+        let noun: Vec<(&str, usize)> = nullable.leftcorners("Noun").iter().map(|(rule, dot)| (rule.lhs(), *dot)).collect();
+        assert_eq!(noun, vec!(("NP", 2)));
+        assert!(nullable.leftcorners("VP").is_empty());
+        assert_eq!(nullable.empty_edges(3).count(), 2);
+        assert!(nullable.empty_edges(3).all(|edge| edge.start() == 3 && edge.end() == 3 && edge.is_passive()));
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::models::{
+    chart::Chart,
+    compiled_grammar::{CompiledGrammar, RuleId, Symbol},
+    edge::Edge,
+};
+use crate::parser::{
+    active_index::ActiveIndex,
+    limits::{Limit, Limits, CHECK_INTERVAL},
+    Diagnosis, Earley3,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Item {
+    pub(crate) rule: RuleId,
+    pub(crate) dot: usize,
+    pub(crate) start: usize,
+}
+
+/// An incremental parse with an `Earley3` parser.
+///
+/// Words are pushed one at a time and each `push` extends the chart by one
+/// position, so the prefix read so far can be queried at any time without
+/// parsing it again.
+///
+/// A session started with `Earley3::session` also keeps track of which
+/// active edges can be part of a `goal` spanning from position 0, which is
+/// what `is_viable` and `expected` are answered from.
+///
+/// A session with `Limits` stops as soon as one of them is exceeded,
+/// leaving the chart as it was at that point.
+pub struct Session<'a> {
+    parser: &'a Earley3<'a>,
+    chart: Chart<'a>,
+    words: Vec<&'a str>,
+    active_index: ActiveIndex<Symbol, Item>,
+    /// Leo items: for position `j` and symbol `B`, the topmost passive
+    /// item reached by completing a `B` starting in `j`, if the way there
+    /// is deterministic.
+    leo_items: Vec<HashMap<Symbol, Option<Item>>>,
+    goal: Option<Goal>,
+    limits: Limits,
+    edges: usize,
+    /// Agenda items popped, counting duplicates, for how often to look at
+    /// the clock.
+    steps: usize,
+    pushes: usize,
+    exceeded: Option<Limit>,
+    // Scratch space for `push`, kept to reuse the allocations.
+    items: HashSet<Item>,
+    active: Vec<(Symbol, Item)>,
+    agenda: Vec<Item>,
+}
+
+struct Goal {
+    symbol: Option<Symbol>,
+    /// For each position, the categories that can start there as part of
+    /// a goal spanning from 0.
+    reachable: Vec<HashSet<Symbol>>,
+    /// The symbols waited for by such edges in the last position.
+    expected: Vec<Symbol>,
+    /// The last position where the words were a goal or the start of
+    /// one, with the edges waiting there and the symbols they expect.
+    furthest: usize,
+    stuck: Vec<Item>,
+    stuck_expected: Vec<Symbol>,
+}
+
+impl<'a> Session<'a> {
+    pub(crate) fn new(parser: &'a Earley3<'a>, goal: Option<&str>) -> Self {
+        let goal = goal.map(|goal| {
+            let symbol = parser.compiled.symbols().get(goal);
+            Goal {
+                symbol,
+                reachable: vec!(
+                    symbol.iter()
+                        .flat_map(|&goal| parser.compiled.left_descendants(goal))
+                        .copied()
+                        .collect()
+                ),
+                expected: symbol.into_iter().collect(),
+                furthest: 0,
+                stuck: Vec::new(),
+                stuck_expected: symbol.into_iter().collect(),
+            }
+        });
+        let mut chart = Chart::new();
+        chart.add_position();
+        add_empty_edges(parser, &mut chart, 0);
+        let mut active_index = ActiveIndex::new();
+        active_index.push_position(&mut Vec::new());
+        Session {
+            parser,
+            chart,
+            words: Vec::new(),
+            active_index,
+            leo_items: vec!(HashMap::new()),
+            goal,
+            limits: Limits::default(),
+            edges: 0,
+            steps: 0,
+            pushes: 0,
+            exceeded: None,
+            items: HashSet::new(),
+            active: Vec::new(),
+            agenda: Vec::new(),
+        }
+    }
+
+    /// Stops the session when one of the `limits` is exceeded.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Extends the chart with the next word.
+    ///
+    /// Once a limit is exceeded the session ignores further words; use
+    /// `try_push` to find out when that happens.
+    pub fn push(&mut self, word: &'a str) {
+        let _ = self.try_push(word);
+    }
+
+    /// Extends the chart with the next word, or returns the limit that
+    /// stopped the session.
+    pub fn try_push(&mut self, word: &'a str) -> Result<(), Limit> {
+        if let Some(limit) = self.exceeded {
+            return Err(limit);
+        }
+        if let Err(limit) = self.limits.check(self.edges, self.pushes, true) {
+            self.exceeded = Some(limit);
+            return Err(limit);
+        }
+        let k = self.chart.len();
+        self.chart.add_position();
+        self.words.push(word);
+        self.items.clear();
+        let mut agenda = std::mem::take(&mut self.agenda);
+
+        // Scan
+        self.chart.add_edge(Edge::new(k-1, k, word, None, 0));
+        if let Some(sym) = self.parser.compiled.symbols().get(word) {
+            self.found(sym, k-1, &mut agenda);
+        }
+        self.pushes += agenda.len();
+
+        while let Some(item) = agenda.pop() {
+            if self.items.insert(item) {
+                self.edges += 1;
+                let rule = self.parser.compiled.rule(item.rule);
+                let before = agenda.len();
+                match rule.rhs().get(item.dot) {
+                    None => {
+                        self.found(rule.lhs(), item.start, &mut agenda);
+                        self.chart.add_edge(
+                            Edge::from_rule(item.start, k, &self.parser.grammar.rules[item.rule], item.dot)
+                        );
+                    }
+                    Some(&next) => {
+                        if self.parser.compiled.is_nullable(next) {
+                            agenda.push(Item { dot: item.dot + 1, ..item });
+                        }
+                        self.active.push((next, item));
+                    }
+                }
+                self.pushes += agenda.len() - before;
+            }
+            self.steps += 1;
+            let clock = self.steps % CHECK_INTERVAL == 0;
+            if let Err(limit) = self.limits.check(self.edges, self.pushes, clock) {
+                self.exceeded = Some(limit);
+                agenda.clear();
+            }
+        } // while agenda
+        self.agenda = agenda;
+        add_empty_edges(self.parser, &mut self.chart, k);
+        self.update_goal();
+        self.active_index.push_position(&mut self.active);
+        self.leo_items.push(HashMap::new());
+        self.exceeded.map_or(Ok(()), Err)
+    }
+
+    /// The limit that stopped the session, if any.
+    pub fn exceeded(&self) -> Option<Limit> {
+        self.exceeded
+    }
+
+    /// The number of words pushed so far.
+    pub fn len(&self) -> usize {
+        self.chart.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn chart(&self) -> &Chart<'a> {
+        &self.chart
+    }
+
+    pub fn into_chart(self) -> Chart<'a> {
+        self.chart
+    }
+
+    /// The passive edges ending after the last word.
+    pub fn completed(&self) -> &[Edge<'a>] {
+        self.chart.edgeset(self.len())
+    }
+
+    /// Whether the words so far form a goal.
+    pub fn is_complete(&self) -> bool {
+        match &self.goal {
+            Some(Goal { symbol: Some(goal), .. }) => {
+                let goal = self.parser.compiled.symbols().name(*goal);
+                self.completed().iter().any(|edge| edge.start() == 0 && edge.lhs() == goal)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the words so far are a goal or the start of one.
+    pub fn is_viable(&self) -> bool {
+        self.goal.as_ref().is_some_and(|goal| !goal.expected.is_empty()) || self.is_complete()
+    }
+
+    /// The categories that can continue the words so far towards a goal.
+    pub fn expected(&self) -> Vec<&str> {
+        let symbols = self.parser.compiled.symbols();
+        let mut expected: Vec<&str> = self.goal.iter()
+            .flat_map(|goal| goal.expected.iter())
+            .map(|&sym| symbols.name(sym))
+            .collect();
+        expected.sort_unstable();
+        expected
+    }
+
+    /// The words that can come next and continue the words so far towards
+    /// a goal.
+    pub fn next_words(&self) -> Vec<&str> {
+        self.next_symbols(|compiled, sym| compiled.is_terminal(sym))
+    }
+
+    /// The lexical categories that the next word can have.
+    pub fn next_preterminals(&self) -> Vec<&str> {
+        self.next_symbols(|compiled, sym| compiled.is_preterminal(sym))
+    }
+
+    /// The symbols that can start an expected category and satisfy `filter`.
+    fn next_symbols(&self, filter: impl Fn(&CompiledGrammar, Symbol) -> bool) -> Vec<&str> {
+        let compiled = &self.parser.compiled;
+        let mut found: Vec<&str> = self.goal.iter()
+            .flat_map(|goal| goal.expected.iter())
+            .flat_map(|&sym| compiled.left_descendants(sym))
+            .filter(|&&sym| filter(compiled, sym))
+            .map(|&sym| compiled.symbols().name(sym))
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    /// Explains why the words so far are not a goal, or returns `None` if
+    /// they are.
+    ///
+    /// The diagnosis points at the furthest position reached by edges
+    /// that can be part of a goal from position 0, with the edges waiting
+    /// there and the token that none of them could use.
+    pub fn diagnose(&self) -> Option<Diagnosis<'a>> {
+        if self.is_complete() {
+            return None;
+        }
+        let parser = self.parser;
+        let mut diagnosis = Diagnosis {
+            position: 0,
+            token: self.words.first().copied(),
+            stuck: Vec::new(),
+            expected: Vec::new(),
+        };
+        if let Some(goal) = &self.goal {
+            diagnosis.position = goal.furthest;
+            diagnosis.token = self.words.get(goal.furthest).copied();
+            diagnosis.stuck = goal.stuck.iter()
+                .map(|item| {
+                    Edge::from_rule(item.start, goal.furthest, &parser.grammar.rules[item.rule], item.dot)
+                })
+                .collect();
+            diagnosis.stuck.sort();
+            diagnosis.expected = goal.stuck_expected.iter()
+                .map(|&sym| parser.compiled.symbols().name(sym))
+                .collect();
+            diagnosis.expected.sort_unstable();
+        }
+        Some(diagnosis)
+    }
+
+    /// Records which of the new active edges are part of a goal.
+    fn update_goal(&mut self) {
+        let compiled = &self.parser.compiled;
+        let complete = self.is_complete();
+        let k = self.len();
+        if let Some(goal) = &mut self.goal {
+            let mut expected = Vec::new();
+            let mut stuck = Vec::new();
+            for (next, item) in &self.active {
+                let lhs = compiled.rule(item.rule).lhs();
+                if goal.reachable[item.start].contains(&lhs) {
+                    stuck.push(*item);
+                    if !expected.contains(next) {
+                        expected.push(*next);
+                    }
+                }
+            }
+            if !expected.is_empty() || complete {
+                goal.furthest = k;
+                goal.stuck = stuck;
+                goal.stuck_expected = expected.clone();
+            }
+            goal.reachable.push(
+                expected.iter()
+                    .flat_map(|&sym| compiled.left_descendants(sym))
+                    .copied()
+                    .collect()
+            );
+            goal.expected = expected;
+        }
+    }
+
+    /// Predicts and completes from a passive `symbol` spanning from `start`.
+    fn found(&mut self, symbol: Symbol, start: usize, agenda: &mut Vec<Item>) {
+        let compiled = &self.parser.compiled;
+        // Predict
+        for &rule in compiled.leftcorners(symbol) {
+            agenda.push(Item { rule, dot: 1, start });
+        }
+        for &(rule, dot) in compiled.nullable_leftcorners(symbol) {
+            agenda.push(Item { rule, dot, start });
+        }
+
+        // Complete
+        if self.parser.leo && compiled.nullable_rules().is_empty() {
+            if let Some(item) = self.leo_item(start, symbol) {
+                agenda.push(item);
+                return;
+            }
+        }
+        for (_, item) in self.active_index.get(start, symbol) {
+            agenda.push(Item { dot: item.dot + 1, ..*item });
+        }
+    }
+
+    /// Follows the deterministic reduction path from `symbol` in `start`.
+    ///
+    /// A step is deterministic when exactly one active edge in the position
+    /// is waiting for the symbol and the symbol is the last one of its rule.
+    /// The path continues through the completed edge as long as its
+    /// category is not a left corner of any rule, since such an edge could
+    /// otherwise start new predictions that we would miss by skipping it.
+    fn leo_item(&mut self, start: usize, symbol: Symbol) -> Option<Item> {
+        let compiled = &self.parser.compiled;
+        let mut path = Vec::new();
+        let (mut pos, mut sym) = (start, symbol);
+        let mut top = loop {
+            if let Some(&memo) = self.leo_items[pos].get(&sym) {
+                break memo;
+            }
+            let waiting = self.active_index.get(pos, sym);
+            let item = match waiting {
+                [(_, item)] if item.dot + 1 == compiled.rule(item.rule).rhs().len() => *item,
+                _ => {
+                    self.leo_items[pos].insert(sym, None);
+                    break None;
+                }
+            };
+            let lhs = compiled.rule(item.rule).lhs();
+            path.push((pos, sym, Item { dot: item.dot + 1, ..item }));
+            if !compiled.leftcorners(lhs).is_empty() {
+                break None;
+            }
+            pos = item.start;
+            sym = lhs;
+        };
+        for (pos, sym, completed) in path.into_iter().rev() {
+            let item = top.unwrap_or(completed);
+            self.leo_items[pos].insert(sym, Some(item));
+            top = Some(item);
+        }
+        top
+    }
+}
+
+/// Adds the edges for the rules deriving the empty string in position `k`.
+fn add_empty_edges<'a>(parser: &'a Earley3, chart: &mut Chart<'a>, k: usize) {
+    for &rule in parser.compiled.nullable_rules() {
+        let rule = &parser.grammar.rules[rule];
+        chart.add_edge(Edge::from_rule(k, k, rule, rule.rhs().len()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{example, example_grammar, Earley3, Parser};
+
+    #[test]
+    fn pushing_words_builds_the_same_chart_as_parse() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let sentence = example(2);
+        let mut session = parser.session("S");
+        for word in &sentence {
+            session.push(word);
+        }
+
+        assert_eq!(session.len(), sentence.len());
+        assert_eq!(session.chart().chartsize(), parser.parse(&sentence).chartsize());
+    }
+
+    #[test]
+    fn session_tracks_viable_prefixes() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let mut session = parser.session("S");
+
+        assert!(session.is_viable());
+        assert_eq!(session.expected(), vec!("S"));
+
+        session.push("the");
+        assert!(session.is_viable());
+        assert!(!session.is_complete());
+        assert_eq!(session.expected(), vec!("Noun"));
+
+        session.push("lion");
+        assert_eq!(session.expected(), vec!("PP", "VP"));
+
+        session.push("sees");
+        assert!(session.is_complete());
+        assert_eq!(session.expected(), vec!("NP", "PP"));
+        let mut completed: Vec<&str> = session.completed().iter().map(|e| e.lhs()).collect();
+        completed.sort_unstable();
+        assert_eq!(completed, vec!("S", "VP", "Verb", "sees"));
+
+        session.push("sees");
+        assert!(!session.is_viable());
+        assert!(session.expected().is_empty());
+    }
+
+    #[test]
+    fn session_predicts_next_words() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let mut session = parser.session("S");
+
+        assert_eq!(session.next_words(), vec!("a", "the"));
+        assert_eq!(session.next_preterminals(), vec!("Det"));
+
+        session.push("the");
+        assert_eq!(session.next_words(), vec!("lion", "park", "telescope", "tree", "zebra"));
+
+        session.push("lion");
+        assert_eq!(session.next_words(), vec!("in", "sees", "under", "with"));
+        assert_eq!(session.next_preterminals(), vec!("Prep", "Verb"));
+
+        session.push("in");
+        assert_eq!(session.next_words(), vec!("a", "the"));
+    }
+
+    #[test]
+    fn diagnose_points_at_the_offending_token() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let mut session = parser.session("S");
+        for word in &["the", "lion", "sees", "a", "zebra", "under", "under", "a", "tree"] {
+            session.push(word);
+        }
+        let diagnosis = session.diagnose().unwrap();
+
+        assert_eq!(diagnosis.position, 6);
+        assert_eq!(diagnosis.token, Some("under"));
+        assert_eq!(diagnosis.expected, vec!("NP"));
+        let stuck: Vec<String> = diagnosis.stuck.iter().map(|edge| edge.to_string()).collect();
+        assert_eq!(stuck, vec!("[5-6: PP --> Prep . NP]"));
+    }
+
+    #[test]
+    fn diagnose_incomplete_input() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let mut session = parser.session("S");
+        for word in &["the", "lion"] {
+            session.push(word);
+        }
+        let diagnosis = session.diagnose().unwrap();
+
+        assert_eq!(diagnosis.position, 2);
+        assert_eq!(diagnosis.token, None);
+        assert_eq!(diagnosis.expected, vec!("PP", "VP"));
+        assert_eq!(diagnosis.stuck.len(), 2);
+    }
+
+    #[test]
+    fn diagnose_complete_input_is_none() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let mut session = parser.session("S");
+        for word in &["the", "lion", "sees"] {
+            session.push(word);
+        }
+
+        assert!(session.diagnose().is_none());
+    }
+
+    #[test]
+    fn prefix_that_cannot_start_a_goal_is_not_viable() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let mut session = parser.session("S");
+        session.push("lion");
+
+        assert!(!session.is_viable());
+    }
+
+    #[test]
+    fn unknown_goal_is_never_viable() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let mut session = parser.session("Utterance");
+        session.push("the");
+
+        assert!(!session.is_viable());
+        assert!(session.expected().is_empty());
+        assert!(session.next_words().is_empty());
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::domain::models::{
+    compiled_grammar::{CompiledGrammar, RuleId, Symbol},
+    grammar::Grammar,
+};
+use crate::error::{GrammarError, Result};
+
+/// Stolcke's probabilistic Earley parser.
+///
+/// The rule weights are read as probabilities, i.e. the weights of the
+/// rules for a category must sum to 1. Parsing is top-down, and every
+/// state in the chart carries a forward probability (of the prefix and the
+/// derivation leading to the state) and an inner probability (of the part
+/// of the input the state spans). Chains of left-corner predictions and of
+/// unit rules are collapsed with the precomputed closures
+/// `R_L = (I - P_L)^-1` and `R_U = (I - P_U)^-1`, so left recursion and
+/// unit cycles are summed over exactly. Rules with an empty right-hand
+/// side are not supported. The rules compiled from EBNF operators keep
+/// the sums of the weights, see `ebnf`, so they need no normalizing.
+pub struct Stolcke {
+    compiled: CompiledGrammar,
+    /// For each expected symbol `Z`, the rules `Y -> v` to predict, with
+    /// `R_L(Z, Y) * P(Y -> v)`.
+    predictions: Vec<Vec<(RuleId, f64)>>,
+    /// The row and column of each nonterminal in `unit_closure`.
+    nonterminals: Vec<Option<usize>>,
+    /// `R_U(Z, Y)`, the probability that `Z` rewrites to `Y` by unit rules.
+    unit_closure: Vec<Vec<f64>>,
+}
+
+impl Stolcke {
+    /// Prepares the closures for `grammar`, or fails if the weights of a
+    /// category don't sum to 1 or the closures don't exist (e.g. for a
+    /// left-recursive category that never ends).
+    pub fn from_grammar(grammar: &Grammar) -> Result<Self> {
+        grammar.validate()?;
+        if let Some(rule) = grammar.rules.iter().position(|rule| rule.rhs().is_empty()) {
+            return Err(GrammarError::EmptyRhs { rule, lhs: grammar.rules[rule].lhs().to_string() }.into());
+        }
+        let compiled = CompiledGrammar::compile(grammar);
+        let n = compiled.symbols().len();
+        let mut nonterminals = vec!(None; n);
+        let mut m = 0;
+        for sym in (0..n).filter(|&sym| !compiled.is_terminal(sym)) {
+            let total: f64 = compiled.expansions(sym).iter().map(|&rule| compiled.rule(rule).weight()).sum();
+            if (total - 1.0).abs() > 1e-6 {
+                let lhs = compiled.symbols().name(sym).to_string();
+                return Err(GrammarError::WeightSum { lhs, total: (total * 1e6).round() / 1e6 }.into());
+            }
+            nonterminals[sym] = Some(m);
+            m += 1;
+        }
+        let mut left_corner = vec!(vec!(0.0; m); m);
+        let mut unit = vec!(vec!(0.0; m); m);
+        for rule in compiled.rules() {
+            let lhs = nonterminals[rule.lhs()];
+            if let (Some(lhs), Some(first)) = (lhs, rule.rhs().first().and_then(|&first| nonterminals[first])) {
+                left_corner[lhs][first] += rule.weight();
+                if rule.rhs().len() == 1 {
+                    unit[lhs][first] += rule.weight();
+                }
+            }
+        }
+        let left_corner = closure(left_corner).ok_or(GrammarError::Unnormalizable)?;
+        let unit_closure = closure(unit).ok_or(GrammarError::Unnormalizable)?;
+        let mut predictions = vec!(Vec::new(); n);
+        for (z, &row) in nonterminals.iter().enumerate() {
+            let row = match row {
+                Some(row) => &left_corner[row],
+                None => continue,
+            };
+            for (y, &column) in nonterminals.iter().enumerate() {
+                let r = column.map_or(0.0, |column| row[column]);
+                if r > 0.0 {
+                    for &rule in compiled.expansions(y) {
+                        predictions[z].push((rule, r * compiled.rule(rule).weight()));
+                    }
+                }
+            }
+        }
+        Ok(Stolcke { compiled, predictions, nonterminals, unit_closure })
+    }
+
+    /// Starts an incremental parse looking for a `goal` from position 0.
+    pub fn session(&self, goal: &str) -> StolckeSession<'_> {
+        StolckeSession::new(self, goal)
+    }
+
+    /// The probability that a `goal` starts with `words`.
+    pub fn prefix_probability(&self, goal: &str, words: &[&str]) -> f64 {
+        let mut session = self.session(goal);
+        for word in words {
+            session.push(word);
+        }
+        session.prefix_probability()
+    }
+
+    /// `R_U(z, y)`, which is 0 unless both are nonterminals.
+    fn unit_closure(&self, z: Symbol, y: Symbol) -> f64 {
+        match (self.nonterminals[z], self.nonterminals[y]) {
+            (Some(z), Some(y)) => self.unit_closure[z][y],
+            _ => 0.0,
+        }
+    }
+
+    fn is_unit(&self, rule: RuleId) -> bool {
+        match self.compiled.rule(rule).rhs() {
+            [sym] => !self.compiled.is_terminal(*sym),
+            _ => false,
+        }
+    }
+}
+
+/// Returns `(I - p)^-1` computed by Gauss-Jordan elimination.
+#[allow(clippy::needless_range_loop)]
+fn closure(p: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = p.len();
+    let mut a = p;
+    let mut inverse = vec!(vec!(0.0; n); n);
+    for i in 0..n {
+        for j in 0..n {
+            a[i][j] = if i == j { 1.0 - a[i][j] } else { -a[i][j] };
+        }
+        inverse[i][i] = 1.0;
+    }
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inverse.swap(col, pivot);
+        let d = a[col][col];
+        for j in 0..n {
+            a[col][j] /= d;
+            inverse[col][j] /= d;
+        }
+        for row in 0..n {
+            let factor = a[row][col];
+            if row != col && factor != 0.0 {
+                for j in 0..n {
+                    a[row][j] -= factor * a[col][j];
+                    inverse[row][j] -= factor * inverse[col][j];
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct State {
+    rule: RuleId,
+    dot: usize,
+    start: usize,
+    forward: f64,
+    inner: f64,
+}
+
+#[derive(Default)]
+struct Column {
+    states: Vec<State>,
+    index: HashMap<(RuleId, usize, usize), usize>,
+}
+
+impl Column {
+    /// Adds the probabilities to the state, returning its index and
+    /// whether it is new.
+    fn add(&mut self, rule: RuleId, dot: usize, start: usize, forward: f64, inner: f64) -> (usize, bool) {
+        match self.index.get(&(rule, dot, start)) {
+            Some(&i) => {
+                self.states[i].forward += forward;
+                self.states[i].inner += inner;
+                (i, false)
+            }
+            None => {
+                let i = self.states.len();
+                self.states.push(State { rule, dot, start, forward, inner });
+                self.index.insert((rule, dot, start), i);
+                (i, true)
+            }
+        }
+    }
+}
+
+/// An incremental parse with a `Stolcke` parser.
+///
+/// After each pushed word the session knows the prefix probability, the
+/// probability that a goal starts with the words so far, so it can be
+/// used as a language model giving the surprisal of every word.
+pub struct StolckeSession<'a> {
+    parser: &'a Stolcke,
+    goal: Option<Symbol>,
+    columns: Vec<Column>,
+    prefix_probabilities: Vec<f64>,
+}
+
+impl<'a> StolckeSession<'a> {
+    fn new(parser: &'a Stolcke, goal: &str) -> Self {
+        let goal = parser.compiled.symbols().get(goal);
+        let mut column = Column::default();
+        if let Some(goal) = goal {
+            for &(rule, probability) in &parser.predictions[goal] {
+                column.add(rule, 0, 0, probability, parser.compiled.rule(rule).weight());
+            }
+        }
+        StolckeSession {
+            parser,
+            goal,
+            columns: vec!(column),
+            prefix_probabilities: vec!(if goal.is_some() { 1.0 } else { 0.0 }),
+        }
+    }
+
+    /// Extends the chart with the next word.
+    pub fn push(&mut self, word: &str) {
+        let compiled = &self.parser.compiled;
+        let k = self.columns.len();
+        let mut column = Column::default();
+        let mut complete = BinaryHeap::new();
+
+        // Scan
+        let mut prefix_probability = 0.0;
+        if let Some(sym) = compiled.symbols().get(word).filter(|&sym| compiled.is_terminal(sym)) {
+            for state in &self.columns[k-1].states {
+                let rhs = compiled.rule(state.rule).rhs();
+                if rhs.get(state.dot) == Some(&sym) {
+                    let (i, _) = column.add(state.rule, state.dot + 1, state.start, state.forward, state.inner);
+                    prefix_probability += state.forward;
+                    if state.dot + 1 == rhs.len() {
+                        complete.push((state.start, i));
+                    }
+                }
+            }
+        }
+
+        // Complete, in order of decreasing start so that the inner
+        // probability of a state is final before the state is used
+        while let Some((start, i)) = complete.pop() {
+            let trigger = column.states[i];
+            let lhs = compiled.rule(trigger.rule).lhs();
+            for state in &self.columns[start].states {
+                let rhs = compiled.rule(state.rule).rhs();
+                let r = match rhs.get(state.dot) {
+                    Some(&next) => self.parser.unit_closure(next, lhs),
+                    None => continue,
+                };
+                if r == 0.0 {
+                    continue;
+                }
+                let (j, new) = column.add(
+                    state.rule,
+                    state.dot + 1,
+                    state.start,
+                    state.forward * r * trigger.inner,
+                    state.inner * r * trigger.inner,
+                );
+                if new && state.dot + 1 == rhs.len() && !self.parser.is_unit(state.rule) {
+                    complete.push((state.start, j));
+                }
+            }
+        }
+
+        // Predict
+        let mut predicted = Vec::new();
+        for state in &column.states {
+            if let Some(&next) = compiled.rule(state.rule).rhs().get(state.dot) {
+                for &(rule, probability) in &self.parser.predictions[next] {
+                    predicted.push((rule, state.forward * probability));
+                }
+            }
+        }
+        for (rule, forward) in predicted {
+            let (i, new) = column.add(rule, 0, k, forward, 0.0);
+            if new {
+                column.states[i].inner = compiled.rule(rule).weight();
+            }
+        }
+
+        self.columns.push(column);
+        self.prefix_probabilities.push(prefix_probability);
+    }
+
+    /// The number of words pushed so far.
+    pub fn len(&self) -> usize {
+        self.columns.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The probability that a goal starts with the words so far.
+    pub fn prefix_probability(&self) -> f64 {
+        self.prefix_probabilities[self.len()]
+    }
+
+    /// The probability that the words so far form a goal.
+    pub fn sentence_probability(&self) -> f64 {
+        let goal = match self.goal {
+            Some(goal) => goal,
+            None => return 0.0,
+        };
+        let compiled = &self.parser.compiled;
+        self.columns[self.len()].states.iter()
+            .filter(|state| state.start == 0 && state.dot == compiled.rule(state.rule).rhs().len())
+            .filter(|state| !self.parser.is_unit(state.rule))
+            .map(|state| self.parser.unit_closure(goal, compiled.rule(state.rule).lhs()) * state.inner)
+            .sum()
+    }
+
+    /// The surprisal `-log2 P(word | prefix)` of the last word, in bits.
+    pub fn surprisal(&self) -> f64 {
+        let k = self.len();
+        if k == 0 {
+            return 0.0;
+        }
+        -(self.prefix_probabilities[k] / self.prefix_probabilities[k-1]).log2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::grammar::Rule;
+
+    fn pcfg() -> Grammar {
+        let rule = |lhs: &str, rhs: &[&str], p| {
+            Rule::new(lhs, rhs.iter().map(|x| x.to_string()).collect()).with_weight(p)
+        };
+        Grammar::from_rules(vec!(
+            rule("S", &["NP", "VP"], 1.0),
+            rule("NP", &["Det", "Noun"], 0.8),
+            rule("NP", &["NP", "PP"], 0.2),
+            rule("VP", &["Verb"], 0.4),
+            rule("VP", &["Verb", "NP"], 0.6),
+            rule("PP", &["Prep", "NP"], 1.0),
+            rule("Det", &["the"], 1.0),
+            rule("Noun", &["lion"], 0.5),
+            rule("Noun", &["zebra"], 0.5),
+            rule("Verb", &["sees"], 1.0),
+            rule("Prep", &["with"], 1.0),
+        ))
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn closure_of_zero_matrix_is_identity() {
+        let identity = closure(vec!(vec!(0.0, 0.0), vec!(0.0, 0.0))).unwrap();
+
+        assert_eq!(identity, vec!(vec!(1.0, 0.0), vec!(0.0, 1.0)));
+    }
+
+    #[test]
+    fn closure_sums_geometric_series() {
+        let r = closure(vec!(vec!(0.5))).unwrap();
+
+        assert_close(r[0][0], 2.0);
+    }
+
+    #[test]
+    fn weights_must_sum_to_one_per_category() {
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("NP", vec!("lion".to_string())),
+            Rule::new("NP", vec!("big".to_string(), "lion".to_string())),
+        ));
+
+        match Stolcke::from_grammar(&grammar) {
+            Err(crate::Error::Grammar(GrammarError::WeightSum { lhs, total })) => {
+                assert_eq!((lhs.as_str(), total), ("NP", 2.0));
+            }
+            other => panic!("unexpected result {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn ebnf_rules_keep_their_probabilities() {
+        let grammar = crate::io::text::parse_grammar("S -> Det? Adj* Noun\nDet -> \"the\"\nAdj -> \"big\"\nNoun -> \"lion\"\n").unwrap();
+        let parser = Stolcke::from_grammar(&grammar).unwrap();
+        let mut session = parser.session("S");
+        for word in ["big", "big", "lion"] {
+            session.push(word);
+        }
+
+        assert_close(session.sentence_probability(), 0.5 * 0.5 * 0.25);
+    }
+
+    #[test]
+    fn endless_left_recursion_has_no_closure() {
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("NP", vec!("NP".to_string(), "PP".to_string())),
+            Rule::new("PP", vec!("with".to_string())),
+        ));
+
+        assert!(matches!(
+            Stolcke::from_grammar(&grammar),
+            Err(crate::Error::Grammar(GrammarError::Unnormalizable))
+        ));
+    }
+
+    #[test]
+    fn prefix_probabilities() {
+        let grammar = pcfg();
+        let parser = Stolcke::from_grammar(&grammar).unwrap();
+
+        assert_close(parser.prefix_probability("S", &[]), 1.0);
+        assert_close(parser.prefix_probability("S", &["the"]), 1.0);
+        assert_close(parser.prefix_probability("S", &["the", "lion"]), 0.5);
+        assert_close(parser.prefix_probability("S", &["the", "lion", "sees"]), 0.4);
+        assert_close(parser.prefix_probability("S", &["the", "lion", "with"]), 0.1);
+        assert_close(parser.prefix_probability("S", &["the", "lion", "sees", "the"]), 0.24);
+        assert_close(parser.prefix_probability("S", &["lion"]), 0.0);
+        assert_close(parser.prefix_probability("S", &["the", "unicorn"]), 0.0);
+    }
+
+    #[test]
+    fn session_tracks_sentence_probability_and_surprisal() {
+        let grammar = pcfg();
+        let parser = Stolcke::from_grammar(&grammar).unwrap();
+        let mut session = parser.session("S");
+
+        session.push("the");
+        assert_close(session.surprisal(), 0.0);
+        assert_close(session.sentence_probability(), 0.0);
+
+        session.push("lion");
+        assert_close(session.surprisal(), 1.0);
+
+        session.push("sees");
+        assert_eq!(session.len(), 3);
+        assert_close(session.sentence_probability(), 0.8 * 0.5 * 0.4);
+        assert_close(session.prefix_probability(), 0.4);
+
+        session.push("the");
+        session.push("zebra");
+        assert_close(session.sentence_probability(), 0.8 * 0.5 * 0.6 * 0.8 * 0.5);
+    }
+
+    #[test]
+    fn unknown_goal_has_probability_zero() {
+        let grammar = pcfg();
+        let parser = Stolcke::from_grammar(&grammar).unwrap();
+
+        assert_close(parser.prefix_probability("Utterance", &[]), 0.0);
+        assert_close(parser.prefix_probability("Utterance", &["the"]), 0.0);
+    }
+}
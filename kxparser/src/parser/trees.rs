@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use crate::domain::models::{
+    chart::Chart,
+    edge::Edge,
+    grammar::Grammar,
+    tree::Tree,
+};
+
+/// A tree with its log weight.
+type WeightedTree = (f64, Tree);
+
+/// Builds trees from the passive edges of a chart.
+///
+/// The chart only holds passive edges, so the children of an edge are
+/// found again by looking for passive edges for each symbol of its
+/// right-hand side that together cover the span of the edge. When there
+/// are several ways to do that, the one with the highest weight (the
+/// product of the rule weights in the tree) is chosen.
+///
+/// Charts from `Earley3` with Leo's optimization lack the intermediate
+/// edges of right-recursive chains, and trees above them can't be built.
+pub struct TreeBuilder<'c, 'a> {
+    /// The passive edges, by start and end position.
+    spans: HashMap<(usize, usize), Vec<&'c Edge<'a>>>,
+    /// The log weight of each rule.
+    weights: HashMap<(&'a str, &'a [String]), f64>,
+    /// The best tree for a category over a span, `None` if there is none.
+    memo: HashMap<(&'a str, usize, usize), Option<WeightedTree>>,
+    /// The categories and spans whose trees are being built, which have no
+    /// tree while they are, to stop at unit cycles.
+    stack: Vec<(&'a str, usize, usize)>,
+    /// The lowest position in `stack` that the tree being built met. A
+    /// tree that met one below its own position may miss the trees
+    /// through it, so it is not kept in `memo`.
+    lowest: usize,
+}
+
+impl<'c, 'a> TreeBuilder<'c, 'a> {
+    pub fn new(grammar: &'a Grammar, chart: &'c Chart<'a>) -> Self {
+        let mut spans = HashMap::new();
+        for edge in chart.edges().iter().filter(|edge| edge.is_passive()) {
+            spans.entry((edge.start(), edge.end())).or_insert_with(Vec::new).push(edge);
+        }
+        let mut weights = HashMap::new();
+        for rule in &grammar.rules {
+            let weight = weights.entry((rule.lhs(), rule.rhs())).or_insert(f64::NEG_INFINITY);
+            *weight = rule.weight().ln().max(*weight);
+        }
+        TreeBuilder { spans, weights, memo: HashMap::new(), stack: Vec::new(), lowest: usize::MAX }
+    }
+
+    /// The best tree for `cat` spanning `start`-`end`.
+    pub fn tree(&mut self, cat: &'a str, start: usize, end: usize) -> Option<Tree> {
+        self.weighted_tree(cat, start, end).map(|(_, tree)| tree)
+    }
+
+    /// The best tree for `cat` spanning `start`-`end`, with its log weight.
+    ///
+    /// The auxiliary categories of EBNF rules are flattened away.
+    pub fn weighted_tree(&mut self, cat: &'a str, start: usize, end: usize) -> Option<WeightedTree> {
+        self.best(cat, start, end).map(|(weight, tree)| (weight, tree.flatten()))
+    }
+
+    fn best(&mut self, cat: &'a str, start: usize, end: usize) -> Option<WeightedTree> {
+        let key = (cat, start, end);
+        if let Some(found) = self.memo.get(&key) {
+            return found.clone();
+        }
+        if let Some(depth) = self.stack.iter().position(|other| *other == key) {
+            self.lowest = self.lowest.min(depth);
+            return None;
+        }
+        let depth = self.stack.len();
+        let outer = std::mem::replace(&mut self.lowest, usize::MAX);
+        self.stack.push(key);
+        let edges: Vec<&Edge<'a>> = self.spans.get(&(start, end))
+            .map(|edges| edges.iter().filter(|edge| edge.lhs() == cat).copied().collect())
+            .unwrap_or_default();
+        let mut best: Option<WeightedTree> = None;
+        for edge in edges {
+            let weight = self.weights.get(&(edge.lhs(), edge.rhs())).copied().unwrap_or(0.0);
+            let found = if !edge.rhs().is_empty() {
+                self.children(edge.rhs(), start, end).map(|(children_weight, children)| {
+                    (weight + children_weight, Tree::new(cat, children))
+                })
+            } else if start == end {
+                Some((weight, Tree::new(cat, Vec::new())))
+            } else {
+                Some((0.0, Tree::leaf(cat)))
+            };
+            if let Some((weight, tree)) = found {
+                if best.as_ref().is_none_or(|(best_weight, _)| weight > *best_weight) {
+                    best = Some((weight, tree));
+                }
+            }
+        }
+        self.stack.pop();
+        if self.lowest >= depth {
+            self.memo.insert(key, best.clone());
+            self.lowest = outer;
+        } else {
+            self.lowest = self.lowest.min(outer);
+        }
+        best
+    }
+
+    /// The best sequence of trees for `symbols` covering `start`-`end`.
+    fn children(&mut self, symbols: &'a [String], start: usize, end: usize) -> Option<(f64, Vec<Tree>)> {
+        let (first, rest) = symbols.split_first()?;
+        if rest.is_empty() {
+            return self.best(first, start, end).map(|(weight, tree)| (weight, vec!(tree)));
+        }
+        let mut best: Option<(f64, Vec<Tree>)> = None;
+        for mid in start..=end {
+            if let Some((first_weight, first_tree)) = self.best(first, start, mid) {
+                if let Some((rest_weight, mut trees)) = self.children(rest, mid, end) {
+                    let weight = first_weight + rest_weight;
+                    if best.as_ref().is_none_or(|(best_weight, _)| weight > *best_weight) {
+                        trees.insert(0, first_tree);
+                        best = Some((weight, trees));
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::grammar::Rule;
+    use crate::parser::{example, example_grammar, Earley3, Parser};
+
+    #[test]
+    fn builds_tree_for_sentence() {
+        let grammar = example_grammar();
+        let parser = Earley3::from_grammar(&grammar);
+        let sentence = example(0);
+        let chart = parser.parse(&sentence);
+        let mut builder = TreeBuilder::new(&grammar, &chart);
+
+        assert_eq!(
+            builder.tree("S", 0, 5).unwrap().to_string(),
+            "(S (NP (Det the) (Noun lion)) (VP (Verb sees) (NP (Det a) (Noun zebra))))"
+        );
+        assert_eq!(builder.tree("lion", 1, 2), Some(Tree::leaf("lion")));
+        assert_eq!(builder.tree("S", 0, 4), None);
+    }
+
+    #[test]
+    fn prefers_the_tree_with_highest_weight() {
+        let mut grammar = example_grammar();
+        for rule in &mut grammar.rules {
+            if rule.lhs() == "NP" && rule.rhs()[0] == "NP" {
+                *rule = rule.clone().with_weight(0.1);
+            }
+        }
+        let parser = Earley3::from_grammar(&grammar);
+        let sentence = example(1);
+        let chart = parser.parse(&sentence);
+        let mut builder = TreeBuilder::new(&grammar, &chart);
+
+        assert_eq!(
+            builder.tree("VP", 2, 8).unwrap().to_string(),
+            "(VP (VP (Verb sees) (NP (Det a) (Noun zebra))) (PP (Prep under) (NP (Det a) (Noun tree))))"
+        );
+    }
+
+    #[test]
+    fn empty_rules_are_childless_trees_with_their_weight() {
+        let grammar = crate::io::text::parse_grammar(
+            "S -> Det N\nDet -> [0.1]\nDet -> \"the\" [0.9]\nN -> \"lion\"\n"
+        ).unwrap();
+        let parser = Earley3::from_grammar(&grammar);
+        let chart = parser.parse(&["lion"]);
+        let mut builder = TreeBuilder::new(&grammar, &chart);
+
+        let (weight, tree) = builder.weighted_tree("S", 0, 1).unwrap();
+        assert!((weight.exp() - 0.1).abs() < 1e-9);
+        assert_eq!(tree.to_string(), "(S (Det) (N lion))");
+    }
+
+    #[test]
+    fn unit_cycles_do_not_hide_trees_asked_for_later() {
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("X", vec!("Y".to_string())),
+            Rule::new("Y", vec!("X".to_string())),
+            Rule::new("X", vec!("a".to_string())),
+        ));
+        let parser = Earley3::from_grammar(&grammar);
+        let chart = parser.parse(&["a"]);
+        let mut builder = TreeBuilder::new(&grammar, &chart);
+
+        assert_eq!(builder.tree("X", 0, 1).unwrap().to_string(), "(X a)");
+        assert_eq!(builder.tree("Y", 0, 1).unwrap().to_string(), "(Y (X a))");
+        assert_eq!(builder.tree("X", 0, 1).unwrap().to_string(), "(X a)");
+    }
+
+    #[test]
+    fn trees_of_ebnf_rules_are_flattened() {
+        let grammar = crate::io::text::parse_grammar(
+            "NP -> Det? Adj* Noun\nDet -> \"the\"\nAdj -> \"old\" | \"grey\"\nNoun -> \"lion\"\n"
+        ).unwrap();
+        let parser = Earley3::from_grammar(&grammar);
+        let sentence = ["the", "old", "grey", "lion"];
+        let chart = parser.parse(&sentence);
+        let mut builder = TreeBuilder::new(&grammar, &chart);
+
+        assert_eq!(
+            builder.tree("NP", 0, 4).unwrap().to_string(),
+            "(NP (Det the) (Adj old) (Adj grey) (Noun lion))"
+        );
+        assert!(chart.recognizes("NP", 1, -1));
+    }
+}
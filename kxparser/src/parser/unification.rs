@@ -0,0 +1,449 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::fmt;
+
+use crate::domain::models::{
+    chart::Chart,
+    edge::Edge,
+    features::{Category, Value},
+    grammar::Grammar,
+    tree::Tree,
+};
+use crate::error::Result;
+use crate::parser::{active_index::ActiveIndex, Parser};
+
+/// Ground feature values, or variable bindings, sorted by name.
+type Assignment<'a> = Vec<(&'a str, &'a str)>;
+
+/// Left-corner Earley parser for grammars with feature structures.
+///
+/// The categories of the grammar are written like `NP[num=?n]` and parsed
+/// with `Category::from_str`. A symbol is a category if its name, the part
+/// before any `[`, is the name of a left-hand side; other symbols are
+/// words, taken as they are, so that a word like `[` is not mistaken for
+/// a feature structure.
+///
+/// An active edge carries the bindings of its rule's variables, and
+/// completing it with a passive edge unifies the features of the passive
+/// edge with those the rule expects: constants must be equal, and
+/// variables are bound or must agree with their binding. The features of
+/// a passive edge are those of the left-hand side with the variables
+/// replaced by their values; a variable left unbound leaves its feature
+/// unconstrained. Rules with an empty right-hand side are ignored.
+///
+/// As a `Parser`, it builds a `Chart` of the passive edges labelled with
+/// the category names, without their features, and a category that is not
+/// well-formed is read as its name without features. Use
+/// `try_from_grammar` to have such symbols reported, and the inherent
+/// `parse` for a `FeatureChart` with the features of every edge.
+pub struct FeatureEarley<'g> {
+    grammar: &'g Grammar,
+    rules: Vec<(Category, Vec<Category>)>,
+    leftcorners: HashMap<String, Vec<usize>>,
+}
+
+/// A passive edge of a `FeatureChart`.
+#[derive(Clone, Debug)]
+pub struct FeatureEdge<'a> {
+    start: usize,
+    end: usize,
+    category: &'a str,
+    features: Assignment<'a>,
+    /// The rule of the edge, none for a word.
+    rule: Option<usize>,
+    /// The edges for the right-hand side of the rule, none for a word.
+    children: Vec<usize>,
+}
+
+/// The passive edges found by a `FeatureEarley` parser, ordered by end
+/// position.
+#[derive(Debug)]
+pub struct FeatureChart<'a> {
+    edges: Vec<FeatureEdge<'a>>,
+    /// The index of the first edge ending in each position.
+    offsets: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Item<'a> {
+    rule: usize,
+    dot: usize,
+    start: usize,
+    bindings: Assignment<'a>,
+    children: Vec<usize>,
+}
+
+impl<'g> FeatureEarley<'g> {
+    /// Reads the categories of the rules of `grammar`, or fails if one of
+    /// them is not well-formed.
+    pub fn try_from_grammar(grammar: &'g Grammar) -> Result<Self> {
+        FeatureEarley::read(grammar, str::parse)
+    }
+
+    fn read<E>(
+        grammar: &'g Grammar,
+        category: impl Fn(&str) -> std::result::Result<Category, E>,
+    ) -> std::result::Result<Self, E> {
+        let names: HashSet<&str> = grammar.rules.iter().map(|rule| name(rule.lhs())).collect();
+        let mut rules = Vec::new();
+        let mut leftcorners = HashMap::new();
+        for rule in &grammar.rules {
+            let lhs = category(rule.lhs())?;
+            let rhs = rule.rhs().iter()
+                .map(|sym| if names.contains(name(sym)) { category(sym) } else { Ok(Category::new(sym)) })
+                .collect::<std::result::Result<Vec<Category>, E>>()?;
+            if let Some(first) = rhs.first() {
+                leftcorners.entry(first.name().to_string()).or_insert_with(Vec::new).push(rules.len());
+            }
+            rules.push((lhs, rhs));
+        }
+        Ok(FeatureEarley { grammar, rules, leftcorners })
+    }
+
+    /// Parses `input` into a chart of edges with their features.
+    pub fn parse<'a>(&'a self, input: &[&'a str]) -> FeatureChart<'a> {
+        let mut chart = FeatureChart { edges: Vec::new(), offsets: vec!(0) };
+        let mut active_index: ActiveIndex<&str, Item> = ActiveIndex::new();
+        let mut active = Vec::new();
+        active_index.push_position(&mut active);
+
+        for (k, word) in input.iter().enumerate() {
+            let k = k + 1;
+            chart.offsets.push(chart.edges.len());
+            let mut passives = HashSet::new();
+            let mut items = HashSet::new();
+            let mut agenda = Vec::new();
+
+            // Scan
+            chart.edges.push(FeatureEdge {
+                start: k-1,
+                end: k,
+                category: word,
+                features: Vec::new(),
+                rule: None,
+                children: Vec::new(),
+            });
+            self.found(&chart, chart.edges.len() - 1, &active_index, &mut agenda);
+
+            while let Some(item) = agenda.pop() {
+                let (lhs, rhs) = &self.rules[item.rule];
+                match rhs.get(item.dot) {
+                    None => {
+                        let features = instantiate(lhs, &item.bindings);
+                        if passives.insert((item.start, lhs.name(), features.clone())) {
+                            chart.edges.push(FeatureEdge {
+                                start: item.start,
+                                end: k,
+                                category: lhs.name(),
+                                features,
+                                rule: Some(item.rule),
+                                children: item.children,
+                            });
+                            self.found(&chart, chart.edges.len() - 1, &active_index, &mut agenda);
+                        }
+                    }
+                    Some(next) => {
+                        if items.insert((item.rule, item.dot, item.start, item.bindings.clone())) {
+                            active.push((next.name(), item));
+                        }
+                    }
+                }
+            } // while agenda
+            active_index.push_position(&mut active);
+        } // for input
+        chart
+    }
+
+    /// Predicts and completes from the passive edge with index `edge`.
+    fn found<'a>(
+        &'a self,
+        chart: &FeatureChart<'a>,
+        edge: usize,
+        active_index: &ActiveIndex<&'a str, Item<'a>>,
+        agenda: &mut Vec<Item<'a>>,
+    ) {
+        let passive = &chart.edges[edge];
+        // Predict
+        for &rule in self.leftcorners.get(passive.category).into_iter().flatten() {
+            if let Some(bindings) = unify(&self.rules[rule].1[0], &passive.features, &[]) {
+                agenda.push(Item { rule, dot: 1, start: passive.start, bindings, children: vec!(edge) });
+            }
+        }
+
+        // Complete
+        for (_, item) in active_index.get(passive.start, passive.category) {
+            let expected = &self.rules[item.rule].1[item.dot];
+            if let Some(bindings) = unify(expected, &passive.features, &item.bindings) {
+                let mut children = item.children.clone();
+                children.push(edge);
+                agenda.push(Item { rule: item.rule, dot: item.dot + 1, start: item.start, bindings, children });
+            }
+        }
+    }
+}
+
+impl<'g> Parser<'g> for FeatureEarley<'g> {
+    fn from_grammar(grammar: &'g Grammar) -> Self {
+        let lenient = |sym: &str| Ok::<_, Infallible>(sym.parse().unwrap_or_else(|_| Category::new(name(sym))));
+        match FeatureEarley::read(grammar, lenient) {
+            Ok(parser) => parser,
+            Err(never) => match never {},
+        }
+    }
+
+    fn parse<'a>(&'a self, input: &[&'a str]) -> Chart<'a> {
+        let features = FeatureEarley::parse(self, input);
+        let mut chart = Chart::with_capacity(features.len(), features.edges.len());
+        chart.add_position();
+        let mut edgeset = HashSet::new();
+        for edge in features.edges() {
+            while chart.len() <= edge.end {
+                chart.add_position();
+                edgeset.clear();
+            }
+            let edge = match edge.rule {
+                None => Edge::new(edge.start, edge.end, edge.category, None, 0),
+                Some(rule) => {
+                    let rhs = self.grammar.rules[rule].rhs();
+                    Edge::new(edge.start, edge.end, edge.category, Some(rhs), rhs.len())
+                }
+            };
+            if edgeset.insert(edge) {
+                chart.add_edge(edge);
+            }
+        }
+        chart
+    }
+
+    /// The name of the start category, without its features.
+    fn start(&self) -> Option<&'g str> {
+        self.grammar.start().map(name)
+    }
+}
+
+/// The name of a category, the part of `symbol` before any features.
+fn name(symbol: &str) -> &str {
+    symbol.split('[').next().unwrap_or(symbol).trim()
+}
+
+fn lookup<'a>(assignment: &Assignment<'a>, name: &str) -> Option<&'a str> {
+    assignment.iter().find(|(other, _)| *other == name).map(|(_, value)| *value)
+}
+
+/// Unifies the features `expected` by a rule with the `features` of a
+/// passive edge, extending the rule's `bindings`.
+fn unify<'a>(expected: &'a Category, features: &Assignment<'a>, bindings: &[(&'a str, &'a str)]) -> Option<Assignment<'a>> {
+    let mut bindings = bindings.to_vec();
+    for (name, value) in expected.features() {
+        let actual = match lookup(features, name) {
+            Some(actual) => actual,
+            None => continue,
+        };
+        match value {
+            Value::Atom(atom) if atom != actual => return None,
+            Value::Atom(_) => (),
+            Value::Var(var) => match bindings.binary_search_by(|(other, _)| (*other).cmp(var)) {
+                Ok(i) if bindings[i].1 != actual => return None,
+                Ok(_) => (),
+                Err(i) => bindings.insert(i, (var, actual)),
+            },
+        }
+    }
+    Some(bindings)
+}
+
+/// The features of `category` with the variables replaced by their values.
+fn instantiate<'a>(category: &'a Category, bindings: &Assignment<'a>) -> Assignment<'a> {
+    category.features().iter()
+        .filter_map(|(name, value)| match value {
+            Value::Atom(atom) => Some((name.as_str(), atom.as_str())),
+            Value::Var(var) => lookup(bindings, var).map(|value| (name.as_str(), value)),
+        })
+        .collect()
+}
+
+impl<'a> FeatureEdge<'a> {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn category(&self) -> &'a str {
+        self.category
+    }
+
+    /// The feature values, sorted by feature name.
+    pub fn features(&self) -> &[(&'a str, &'a str)] {
+        &self.features
+    }
+
+    pub fn get(&self, feature: &str) -> Option<&'a str> {
+        lookup(&self.features, feature)
+    }
+}
+
+/// Formats the category with its features, `NP[num=sg]`.
+impl fmt::Display for FeatureEdge<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.category)?;
+        if !self.features.is_empty() {
+            let features: Vec<String> = self.features.iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            write!(f, "[{}]", features.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> FeatureChart<'a> {
+    /// The number of positions, i.e. the number of words plus one.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    pub fn edges(&self) -> &[FeatureEdge<'a>] {
+        &self.edges
+    }
+
+    /// The edges ending in position `end`.
+    pub fn ending_at(&self, end: usize) -> &[FeatureEdge<'a>] {
+        match self.offsets.get(end) {
+            Some(&from) => &self.edges[from..self.offsets.get(end + 1).copied().unwrap_or(self.edges.len())],
+            None => &[],
+        }
+    }
+
+    /// The edges for `cat` spanning from `start` to `end`.
+    pub fn span<'s>(&'s self, cat: &'s str, start: usize, end: usize) -> impl Iterator<Item = &'s FeatureEdge<'a>> {
+        self.ending_at(end).iter().filter(move |edge| edge.start == start && edge.category == cat)
+    }
+
+    /// Whether there is a `cat` edge from `start` to `end`, where a
+    /// negative `end` counts from the last position as in
+    /// `Chart::recognizes`.
+    pub fn recognizes(&self, cat: &str, start: usize, end: i32) -> bool {
+        let end = if end < 0 { self.len() as i64 + end as i64 } else { end as i64 };
+        end >= 0 && self.span(cat, start, end as usize).next().is_some()
+    }
+
+    /// A tree for each `cat` edge from `start` to `end`, with the features
+    /// of each node in its label.
+    pub fn trees(&self, cat: &str, start: usize, end: usize) -> Vec<Tree> {
+        self.span(cat, start, end).map(|edge| self.tree(edge)).collect()
+    }
+
+    fn tree(&self, edge: &FeatureEdge) -> Tree {
+        if edge.rule.is_none() {
+            return Tree::leaf(edge.category);
+        }
+        let children = edge.children.iter().map(|&child| self.tree(&self.edges[child])).collect();
+        Tree::new(&edge.to_string(), children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::grammar::Rule;
+
+    fn agreement_grammar() -> Grammar {
+        let rules: [(&str, &[&str]); 11] = [
+            ("S", &["NP[num=?n]", "VP[num=?n]"]),
+            ("NP[num=?n]", &["Det[num=?n]", "Noun[num=?n]"]),
+            ("VP[num=?n]", &["Verb[num=?n]"]),
+            ("VP[num=?n]", &["Verb[num=?n]", "NP"]),
+            ("Det", &["the"]),
+            ("Det[num=sg]", &["a"]),
+            ("Det[num=pl]", &["some"]),
+            ("Noun[num=sg]", &["lion"]),
+            ("Noun[num=pl]", &["lions"]),
+            ("Verb[num=sg]", &["sees"]),
+            ("Verb[num=pl]", &["see"]),
+        ];
+        Grammar::from_rules(
+            rules.iter()
+                .map(|(lhs, rhs)| Rule::new(lhs, rhs.iter().map(|x| x.to_string()).collect()))
+                .collect()
+        )
+    }
+
+    #[test]
+    fn agreement_is_checked() {
+        let grammar = agreement_grammar();
+        let parser = FeatureEarley::try_from_grammar(&grammar).unwrap();
+
+        assert!(parser.parse(&["the", "lion", "sees"]).recognizes("S", 0, -1));
+        assert!(parser.parse(&["the", "lions", "see", "a", "lion"]).recognizes("S", 0, -1));
+        assert!(!parser.parse(&["the", "lions", "sees"]).recognizes("S", 0, -1));
+        assert!(!parser.parse(&["a", "lions"]).recognizes("NP", 0, -1));
+        assert!(!parser.parse(&["some", "lion", "sees"]).recognizes("S", 0, -1));
+    }
+
+    #[test]
+    fn features_are_carried_on_edges_and_trees() {
+        let grammar = agreement_grammar();
+        let parser = FeatureEarley::try_from_grammar(&grammar).unwrap();
+        let chart = parser.parse(&["the", "lions", "see"]);
+
+        let np: Vec<&FeatureEdge> = chart.span("NP", 0, 2).collect();
+        assert_eq!(np.len(), 1);
+        assert_eq!(np[0].get("num"), Some("pl"));
+        assert_eq!(
+            chart.trees("S", 0, 3).iter().map(Tree::to_string).collect::<Vec<_>>(),
+            vec!("(S (NP[num=pl] (Det the) (Noun[num=pl] lions)) (VP[num=pl] (Verb[num=pl] see)))"),
+        );
+    }
+
+    #[test]
+    fn unbound_variables_leave_features_unconstrained() {
+        let grammar = agreement_grammar();
+        let parser = FeatureEarley::try_from_grammar(&grammar).unwrap();
+        let chart = parser.parse(&["the"]);
+
+        assert_eq!(chart.edges().iter().map(|edge| edge.to_string()).collect::<Vec<_>>(), vec!("the", "Det"));
+    }
+
+    #[test]
+    fn malformed_category_is_an_error() {
+        let grammar = Grammar::from_rules(vec!(Rule::new("NP[num=sg", vec!("lion".to_string()))));
+
+        assert!(FeatureEarley::try_from_grammar(&grammar).is_err());
+        let parser = FeatureEarley::from_grammar(&grammar);
+        assert!(parser.recognize(&["lion"]));
+        assert_eq!(parser.start(), Some("NP"));
+    }
+
+    #[test]
+    fn words_are_not_categories() {
+        let grammar = Grammar::from_rules(vec!(
+            Rule::new("S[q=yes]", vec!("NP".to_string(), "?".to_string())),
+            Rule::new("NP", vec!("[".to_string(), "lion".to_string(), "]".to_string())),
+        ));
+        let parser = FeatureEarley::try_from_grammar(&grammar).unwrap();
+
+        assert!(parser.recognize(&["[", "lion", "]", "?"]));
+        assert!(!parser.recognize(&["lion", "?"]));
+    }
+
+    #[test]
+    fn as_a_parser_it_builds_a_chart_of_categories() {
+        let grammar = agreement_grammar();
+        let parser = FeatureEarley::from_grammar(&grammar);
+        let sentence = ["the", "lions", "see", "a", "lion"];
+
+        let chart = Parser::parse(&parser, &sentence);
+
+        assert!(parser.recognize(&sentence));
+        assert!(!parser.recognize(&["the", "lions", "sees"]));
+        assert_eq!(chart.len(), 6);
+        assert_eq!(chart.span(0, 2).map(|edge| edge.to_string()).collect::<Vec<_>>(), vec!("[0-2: NP --> Det[num=?n] Noun[num=?n] . ]"));
+    }
+}